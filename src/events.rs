@@ -1,5 +1,6 @@
 use crate::state::{
-    AppState, CommitPopupMode, CurrentPanel, RefreshScope, refresh_all_states, refresh_scopes,
+    AppState, BranchTab, CommitPopupMode, CurrentPanel, RebaseAction, RebasePlan, RefreshScope,
+    refresh_all_states, refresh_scopes,
 };
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 
@@ -39,6 +40,137 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                 }
                 return Ok(false);
             }
+            if app.branch_rename_popup_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.branch_rename_popup_open = false;
+                        app.branch_rename_input.clear();
+                    }
+                    KeyCode::Enter => {
+                        app.branch_rename_popup_open = false;
+                        if let Some(idx) = app.branch_state.selected()
+                            && let Some(branch) = app.branches.get(idx)
+                        {
+                            let old = crate::git_branch::normalize_branch_name(&branch.name);
+                            let new = app.branch_rename_input.trim().to_string();
+                            match crate::git_branch::rename_branch(&old, &new) {
+                                Ok(()) => {
+                                    if app.commits_branch.as_deref() == Some(old.as_str()) {
+                                        app.commits_branch = Some(new);
+                                    }
+                                }
+                                Err(e) => {
+                                    app.conflict_popup_open = true;
+                                    app.conflict_message = format!("重命名分支失败。\n{}", e);
+                                }
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Branches,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                        app.branch_rename_input.clear();
+                    }
+                    KeyCode::Backspace => {
+                        app.branch_rename_input.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        app.branch_rename_input.push(c);
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.upstream_popup_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.upstream_popup_open = false;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        let i = app.upstream_state.selected().unwrap_or(0);
+                        app.upstream_state.select(Some(i.saturating_sub(1)));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let i = app.upstream_state.selected().unwrap_or(0);
+                        let last = app.remote_branches.len().saturating_sub(1);
+                        app.upstream_state.select(Some((i + 1).min(last)));
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        app.upstream_popup_open = false;
+                        if let Some(idx) = app.branch_state.selected()
+                            && let Some(branch) = app.branches.get(idx)
+                            && let Some(upstream) = app
+                                .upstream_state
+                                .selected()
+                                .and_then(|i| app.remote_branches.get(i))
+                        {
+                            let name = crate::git_branch::normalize_branch_name(&branch.name);
+                            if let Err(e) = crate::git_branch::set_upstream(&name, &upstream.name) {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("设置 upstream 失败。\n{}", e);
+                            }
+                            refresh_scopes(app, &[RefreshScope::Branches]);
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.branch_delete_popup_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.branch_delete_popup_open = false;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        let i = app.branch_delete_state.selected().unwrap_or(0);
+                        app.branch_delete_state.select(Some(i.saturating_sub(1)));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let i = app.branch_delete_state.selected().unwrap_or(0);
+                        let last = usize::from(app.branch_delete_upstream.is_some());
+                        app.branch_delete_state.select(Some((i + 1).min(last)));
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        app.branch_delete_popup_open = false;
+                        if let Some(idx) = app.branch_state.selected()
+                            && let Some(branch) = app.branches.get(idx)
+                        {
+                            let name = crate::git_branch::normalize_branch_name(&branch.name);
+                            let mut result =
+                                crate::git_branch::delete_branch(&name, app.branch_delete_unmerged);
+                            // option 1 also deletes the upstream branch on its remote, only once
+                            // the local branch is gone so a refused delete loses nothing
+                            if result.is_ok()
+                                && app.branch_delete_state.selected() == Some(1)
+                                && let Some(upstream) = &app.branch_delete_upstream
+                            {
+                                result = crate::git_branch::delete_remote_branch(upstream);
+                            }
+                            if let Err(e) = result {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("删除分支失败。\n{}", e);
+                            }
+                            if app.commits_branch.as_deref() == Some(name.as_str()) {
+                                crate::state::browse_commits(app, None);
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Branches,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
             if app.reset_popup_open {
                 match key.code {
                     KeyCode::Esc => {
@@ -99,6 +231,104 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                 }
                 return Ok(false);
             }
+            if app.diff_search_open {
+                let Some(search) = app.diff_search.as_mut() else {
+                    app.diff_search_open = false;
+                    return Ok(false);
+                };
+                match key.code {
+                    KeyCode::Esc => {
+                        let origin = search.origin;
+                        app.diff_search = None;
+                        app.diff_search_open = false;
+                        app.diff_state.select(Some(origin));
+                        return Ok(false);
+                    }
+                    KeyCode::Enter => {
+                        app.diff_search_open = false;
+                        return Ok(false);
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        search.regex = !search.regex;
+                    }
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        search.ignore_case = !search.ignore_case;
+                    }
+                    KeyCode::Backspace => {
+                        search.query.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        search.query.push(c);
+                    }
+                    _ => return Ok(false),
+                }
+                search.matcher = if search.query.is_empty() {
+                    None
+                } else {
+                    crate::git_diff::search_matcher(&search.query, search.regex, search.ignore_case)
+                };
+                crate::git_diff::update_search_hits(&app.diff, search);
+                // jump to the first match at or after where the search started
+                let target = search
+                    .hits
+                    .iter()
+                    .find(|&&h| h >= search.origin)
+                    .or(search.hits.first())
+                    .copied()
+                    .unwrap_or(search.origin);
+                app.diff_state.select(Some(target));
+                return Ok(false);
+            }
+            if app.diff_options_popup_open {
+                let sel = app.diff_options_state.selected().unwrap_or(0);
+                let opts = &mut app.diff_options;
+                match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('o') => {
+                        app.diff_options_popup_open = false;
+                        return Ok(false);
+                    }
+
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.diff_options_state.select(Some(sel.saturating_sub(1)));
+                        return Ok(false);
+                    }
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.diff_options_state.select(Some(sel.saturating_sub(1)));
+                        return Ok(false);
+                    }
+
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.diff_options_state.select(Some((sel + 1).min(4)));
+                        return Ok(false);
+                    }
+                    KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.diff_options_state.select(Some((sel + 1).min(4)));
+                        return Ok(false);
+                    }
+
+                    KeyCode::Char(' ') => match sel {
+                        0 => opts.ignore_whitespace = !opts.ignore_whitespace,
+                        2 => opts.algorithm = crate::git_diff::next_algorithm(opts.algorithm),
+                        3 => opts.find_renames = !opts.find_renames,
+                        4 => opts.find_copies = !opts.find_copies,
+                        _ => return Ok(false),
+                    },
+                    KeyCode::Char('+') | KeyCode::Char('l') | KeyCode::Right => match sel {
+                        1 => opts.context_lines = (opts.context_lines + 1).min(100),
+                        2 => opts.algorithm = crate::git_diff::next_algorithm(opts.algorithm),
+                        _ => return Ok(false),
+                    },
+                    KeyCode::Char('-') | KeyCode::Char('h') | KeyCode::Left => match sel {
+                        1 => opts.context_lines = opts.context_lines.saturating_sub(1),
+                        2 => opts.algorithm = crate::git_diff::prev_algorithm(opts.algorithm),
+                        _ => return Ok(false),
+                    },
+                    KeyCode::Char('w') => opts.ignore_whitespace = !opts.ignore_whitespace,
+                    _ => return Ok(false),
+                }
+                refresh_scopes(app, &[RefreshScope::Diff]);
+                return Ok(false);
+            }
             if app.push_force_popup_open {
                 match key.code {
                     KeyCode::Esc => {
@@ -119,14 +349,37 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     KeyCode::Esc => {
                         app.commit_popup_open = false;
                         app.commit_input.clear();
+                        app.pending_reword_hash = None;
                     }
                     KeyCode::Enter => {
                         match app.commit_popup_mode {
+                            CommitPopupMode::RebaseReword => {
+                                if let Some(plan) = app.rebase_plan.as_mut()
+                                    && let Some(idx) = app.rebase_state.selected()
+                                    && let Some(entry) = plan.entries.get_mut(idx)
+                                    && !app.commit_input.trim().is_empty()
+                                {
+                                    entry.action = RebaseAction::Reword;
+                                    entry.message = Some(app.commit_input.trim().to_string());
+                                }
+                                app.commit_popup_open = false;
+                                app.commit_input.clear();
+                                return Ok(false);
+                            }
                             CommitPopupMode::New => {
                                 let _ = crate::git_commits::commit(&app.commit_input);
                             }
                             CommitPopupMode::Edit => {
-                                let _ = crate::git_commits::reword_last_commit(&app.commit_input);
+                                if let Some(hash) = app.pending_reword_hash.take()
+                                    && let Err(e) = crate::git_commits::reword_commit(
+                                        &app.commits,
+                                        &hash,
+                                        &app.commit_input,
+                                    )
+                                {
+                                    app.conflict_popup_open = true;
+                                    app.conflict_message = format!("Reword 失败。\n{}", e);
+                                }
                             }
                         }
                         app.commit_popup_open = false;
@@ -157,6 +410,273 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                 }
                 return Ok(false);
             }
+            if app.tag_popup_open {
+                let input = if app.tag_popup_message_focus {
+                    &mut app.tag_message_input
+                } else {
+                    &mut app.tag_name_input
+                };
+                match key.code {
+                    KeyCode::Esc => {
+                        app.tag_popup_open = false;
+                        app.pending_tag_hash = None;
+                    }
+                    KeyCode::Tab => {
+                        app.tag_popup_message_focus = !app.tag_popup_message_focus;
+                    }
+                    KeyCode::Enter => {
+                        app.tag_popup_open = false;
+                        if let Some(hash) = app.pending_tag_hash.take() {
+                            if let Err(e) = crate::git_tag::create_annotated_tag(
+                                &app.tag_name_input,
+                                &app.tag_message_input,
+                                &hash,
+                            ) {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("创建 tag 失败。\n{}", e);
+                            }
+                            refresh_scopes(app, &[RefreshScope::Tags, RefreshScope::Commits]);
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.tag_delete_popup_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.tag_delete_popup_open = false;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        let i = app.tag_delete_state.selected().unwrap_or(0);
+                        app.tag_delete_state.select(Some(i.saturating_sub(1)));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let i = app.tag_delete_state.selected().unwrap_or(0);
+                        app.tag_delete_state
+                            .select(Some((i + 1).min(app.tag_delete_remotes.len())));
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        app.tag_delete_popup_open = false;
+                        if let Some(idx) = app.tag_state.selected()
+                            && let Some(tag) = app.tags.get(idx)
+                        {
+                            let name = tag.name.clone();
+                            // option 0 is local only, the rest map to the remotes
+                            let remote = app
+                                .tag_delete_state
+                                .selected()
+                                .and_then(|i| i.checked_sub(1))
+                                .and_then(|i| app.tag_delete_remotes.get(i));
+                            let mut result = Ok(());
+                            if let Some(remote) = remote {
+                                result = crate::git_tag::delete_remote_tag(remote, &name);
+                            }
+                            if result.is_ok() {
+                                result = crate::git_tag::delete_tag(&name);
+                            }
+                            if let Err(e) = result {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("删除 tag 失败。\n{}", e);
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Tags,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.remote_delete_popup_open {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.remote_delete_popup_open = false;
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('y') => {
+                        app.remote_delete_popup_open = false;
+                        if let Some(idx) = app.remote_branch_state.selected()
+                            && let Some(branch) = app.remote_branches.get(idx)
+                        {
+                            if let Err(e) = crate::git_branch::delete_remote_branch(branch) {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("删除远端分支失败。\n{}", e);
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Branches,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.commit_filter_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.commit_filter_open = false;
+                        if app.commit_filter.is_none() {
+                            app.commit_filter_input.clear();
+                        }
+                    }
+                    KeyCode::Enter => {
+                        app.commit_filter_open = false;
+                        let filter = crate::git_commits::parse_filter(&app.commit_filter_input);
+                        if filter.is_none() {
+                            app.commit_filter_input.clear();
+                        }
+                        crate::state::filter_commits(app, filter);
+                    }
+                    KeyCode::Backspace => {
+                        app.commit_filter_input.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        app.commit_filter_input.push(c);
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.revert_popup_open {
+                let parents = app
+                    .pending_revert_hash
+                    .as_ref()
+                    .and_then(|hash| app.commits.iter().find(|c| &c.hash == hash))
+                    .map_or(0, |c| c.parents.len());
+                match key.code {
+                    KeyCode::Esc => {
+                        app.revert_popup_open = false;
+                        app.pending_revert_hash = None;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        let i = app.revert_state.selected().unwrap_or(0);
+                        app.revert_state.select(Some(i.saturating_sub(1)));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let i = app.revert_state.selected().unwrap_or(0);
+                        app.revert_state
+                            .select(Some((i + 1).min(parents.saturating_sub(1))));
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        app.revert_popup_open = false;
+                        if let Some(hash) = app.pending_revert_hash.take() {
+                            let mainline = app.revert_state.selected().unwrap_or(0) + 1;
+                            revert(app, &hash, Some(mainline));
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if app.operation_popup_open {
+                match key.code {
+                    KeyCode::Esc => {
+                        app.operation_popup_open = false;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        let i = app.operation_state.selected().unwrap_or(0);
+                        app.operation_state.select(Some(i.saturating_sub(1)));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let i = app.operation_state.selected().unwrap_or(0);
+                        app.operation_state.select(Some((i + 1).min(2)));
+                    }
+                    KeyCode::Char('c') => app.operation_state.select(Some(0)),
+                    KeyCode::Char('s') => app.operation_state.select(Some(1)),
+                    KeyCode::Char('a') => app.operation_state.select(Some(2)),
+                    KeyCode::Enter | KeyCode::Char(' ') => {
+                        app.operation_popup_open = false;
+                        if let Some(op) = app.operation {
+                            let step = match app.operation_state.selected() {
+                                Some(1) => "--skip",
+                                Some(2) => "--abort",
+                                _ => "--continue",
+                            };
+                            let _ = crate::git_rebase::run_operation(op, step);
+                            refresh_all_states(app);
+                            report_stopped_operation(app);
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(false);
+            }
+            if let Some(plan) = app.rebase_plan.as_mut() {
+                let len = plan.entries.len();
+                let idx = app.rebase_state.selected().unwrap_or(0).min(len - 1);
+                let set_action = |plan: &mut RebasePlan, action| {
+                    plan.entries[idx].action = action;
+                };
+                match key.code {
+                    KeyCode::Esc => {
+                        app.rebase_plan = None;
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => {
+                        app.rebase_state.select(Some((idx + 1).min(len - 1)));
+                    }
+                    KeyCode::Char('k') | KeyCode::Up => {
+                        app.rebase_state.select(Some(idx.saturating_sub(1)));
+                    }
+                    // entries are listed newest first, so moving down makes a commit older
+                    KeyCode::Char('J') if idx + 1 < len => {
+                        plan.entries.swap(idx, idx + 1);
+                        app.rebase_state.select(Some(idx + 1));
+                    }
+                    KeyCode::Char('K') if idx > 0 => {
+                        plan.entries.swap(idx, idx - 1);
+                        app.rebase_state.select(Some(idx - 1));
+                    }
+                    KeyCode::Char('p') => set_action(plan, RebaseAction::Pick),
+                    KeyCode::Char('e') => set_action(plan, RebaseAction::Edit),
+                    KeyCode::Char('s') => set_action(plan, RebaseAction::Squash),
+                    KeyCode::Char('f') => set_action(plan, RebaseAction::Fixup),
+                    KeyCode::Char('d') => set_action(plan, RebaseAction::Drop),
+                    KeyCode::Char('r') => {
+                        let entry = &plan.entries[idx];
+                        app.commit_input = entry.message.clone().unwrap_or_else(|| {
+                            if entry.commit.body.is_empty() {
+                                entry.commit.subject.clone()
+                            } else {
+                                format!("{}\n\n{}", entry.commit.subject, entry.commit.body)
+                            }
+                        });
+                        app.commit_popup_open = true;
+                        app.commit_popup_mode = CommitPopupMode::RebaseReword;
+                        return Ok(false);
+                    }
+                    KeyCode::Enter => {
+                        let result = crate::git_rebase::run_plan(plan);
+                        app.rebase_plan = None;
+                        refresh_all_states(app);
+                        if !report_stopped_operation(app)
+                            && let Err(e) = result
+                        {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = format!("Rebase 失败。\n{}", e);
+                        }
+                        return Ok(false);
+                    }
+                    _ => return Ok(false),
+                }
+                refresh_scopes(app, &[RefreshScope::Diff]);
+                return Ok(false);
+            }
 
             match key.code {
                 KeyCode::Char('q') => return Ok(true),
@@ -164,8 +684,37 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     let _ = crate::git_status::pull();
                     refresh_all_states(app);
                 }
+                KeyCode::Char('P') if matches!(app.current_panel, CurrentPanel::Tag) => {
+                    if let Some(idx) = app.tag_state.selected()
+                        && let Some(tag) = app.tags.get(idx)
+                    {
+                        let result = match crate::git_branch::default_remote() {
+                            Some(remote) => crate::git_tag::push_tag(&remote, &tag.name),
+                            None => Err(std::io::Error::other("no remote configured")),
+                        };
+                        if let Err(e) = result {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = format!("推送 tag 失败。\n{}", e);
+                        }
+                    }
+                }
                 KeyCode::Char('P') => {
-                    if crate::git_status::push().is_ok() {
+                    // a branch that tracks nothing is published to the default remote
+                    if let Some(branch) = app.branches.iter().find(|b| b.is_current)
+                        && branch.upstream.is_none()
+                    {
+                        let name = crate::git_branch::normalize_branch_name(&branch.name);
+                        let result = match crate::git_branch::default_remote() {
+                            Some(remote) => crate::git_branch::push_set_upstream(&remote, &name),
+                            None => Err(std::io::Error::other("no remote configured")),
+                        };
+                        if let Err(e) = result {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = format!("Push 失败。\n{}", e);
+                            return Ok(false);
+                        }
+                        refresh_all_states(app);
+                    } else if crate::git_status::push().is_ok() {
                         refresh_all_states(app);
                     } else {
                         app.push_force_popup_open = true;
@@ -193,21 +742,488 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     app.commit_input.clear();
                     app.commit_popup_mode = CommitPopupMode::New;
                 }
+                KeyCode::Char('u')
+                    if matches!(app.current_panel, CurrentPanel::Branch)
+                        && !app.remote_branches.is_empty() =>
+                {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                    {
+                        // start from the current upstream, else the remote branch of the same name
+                        let name = crate::git_branch::normalize_branch_name(&branch.name);
+                        let preselect = app
+                            .remote_branches
+                            .iter()
+                            .position(|r| branch.upstream.as_ref() == Some(&r.name))
+                            .or_else(|| app.remote_branches.iter().position(|r| r.branch == name))
+                            .unwrap_or(0);
+                        app.upstream_state.select(Some(preselect));
+                        app.upstream_popup_open = true;
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('f') if matches!(app.current_panel, CurrentPanel::Branch) => {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                    {
+                        // strictly behind: anything else would need a merge or rebase
+                        let message = if branch.is_current {
+                            Some("当前分支请使用 p (pull)".to_owned())
+                        } else if branch.upstream.is_none() || branch.upstream_gone {
+                            Some("该分支没有 upstream".to_owned())
+                        } else if branch.ahead > 0 || branch.behind == 0 {
+                            Some(
+                                "只有落后于 upstream 且没有本地提交的分支\n才能 fast-forward"
+                                    .to_owned(),
+                            )
+                        } else {
+                            let name = crate::git_branch::normalize_branch_name(&branch.name);
+                            crate::git_branch::fast_forward_branch(&name)
+                                .err()
+                                .map(|e| format!("Fast-forward 失败。\n{}", e))
+                        };
+                        if let Some(message) = message {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = message;
+                            return Ok(false);
+                        }
+                        refresh_scopes(
+                            app,
+                            &[
+                                RefreshScope::Branches,
+                                RefreshScope::Commits,
+                                RefreshScope::Diff,
+                            ],
+                        );
+                    }
+                }
+                KeyCode::Char('U') if matches!(app.current_panel, CurrentPanel::Branch) => {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                        && branch.upstream.is_some()
+                    {
+                        let name = crate::git_branch::normalize_branch_name(&branch.name);
+                        if let Err(e) = crate::git_branch::unset_upstream(&name) {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = format!("取消 upstream 失败。\n{}", e);
+                        }
+                        refresh_scopes(app, &[RefreshScope::Branches]);
+                    }
+                }
+                KeyCode::Char('R') if matches!(app.current_panel, CurrentPanel::Branch) => {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                    {
+                        app.branch_rename_input =
+                            crate::git_branch::normalize_branch_name(&branch.name);
+                        app.branch_rename_popup_open = true;
+                        return Ok(false);
+                    }
+                }
                 KeyCode::Char('R') => {
-                    if matches!(app.current_panel, CurrentPanel::Commit) {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && crate::state::is_head_log(app)
+                        && let Some(idx) = app.commit_state.selected()
+                        && let Some(commit) = app.commits.get(idx)
+                    {
+                        let refusal = if commit.parents.len() > 1 {
+                            Some("不能 reword 合并提交")
+                        } else if crate::git_commits::is_on_upstream(&commit.hash) {
+                            Some("该提交已在上游分支中。\nReword 会改写已推送的历史")
+                        } else {
+                            None
+                        };
+                        if let Some(refusal) = refusal {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = refusal.to_owned();
+                            return Ok(false);
+                        }
                         app.commit_popup_open = true;
-                        app.commit_input =
-                            crate::git_commits::get_head_commit_message().unwrap_or_default();
+                        app.commit_input = crate::git_commits::get_commit_message(&commit.hash)
+                            .unwrap_or_default()
+                            .trim_end()
+                            .to_string();
+                        app.pending_reword_hash = Some(commit.hash.clone());
                         app.commit_popup_mode = CommitPopupMode::Edit;
                         return Ok(false);
                     }
                 }
+                KeyCode::Char('/') if matches!(app.current_panel, CurrentPanel::Diff) => {
+                    app.diff_search_open = true;
+                    app.diff_search = Some(crate::state::DiffSearch {
+                        origin: app.diff_state.selected().unwrap_or(0),
+                        ..Default::default()
+                    });
+                    return Ok(false);
+                }
+                KeyCode::Char('n') | KeyCode::Char('N')
+                    if matches!(app.current_panel, CurrentPanel::Diff) =>
+                {
+                    if let Some(search) = &app.diff_search {
+                        let from = app.diff_state.selected().unwrap_or(0);
+                        let forward = key.code == KeyCode::Char('n');
+                        if let Some(hit) = crate::git_diff::next_hit(&search.hits, from, forward) {
+                            app.diff_state.select(Some(hit));
+                        }
+                    }
+                }
+                KeyCode::Char(']')
+                | KeyCode::Char('[')
+                | KeyCode::Char('}')
+                | KeyCode::Char('{')
+                    if matches!(app.current_panel, CurrentPanel::Diff) =>
+                {
+                    let from = app.diff_state.selected().unwrap_or(0);
+                    let forward = matches!(key.code, KeyCode::Char(']') | KeyCode::Char('}'));
+                    let pred = if matches!(key.code, KeyCode::Char(']') | KeyCode::Char('[')) {
+                        crate::git_diff::is_hunk_start
+                    } else {
+                        crate::git_diff::is_file_start
+                    };
+                    if let Some(idx) = crate::git_diff::find_next(&app.diff, from, forward, pred) {
+                        app.diff_state.select(Some(idx));
+                    }
+                }
+                KeyCode::PageDown if matches!(app.current_panel, CurrentPanel::Diff) => {
+                    app.diff_state
+                        .scroll_down_by(app.diff_page_size.saturating_sub(1).max(1));
+                }
+                KeyCode::Char('d')
+                    if matches!(app.current_panel, CurrentPanel::Diff)
+                        && key.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    app.diff_state
+                        .scroll_down_by((app.diff_page_size / 2).max(1));
+                }
+                KeyCode::PageUp if matches!(app.current_panel, CurrentPanel::Diff) => {
+                    app.diff_state
+                        .scroll_up_by(app.diff_page_size.saturating_sub(1).max(1));
+                }
+                KeyCode::Char('u')
+                    if matches!(app.current_panel, CurrentPanel::Diff)
+                        && key.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    app.diff_state.scroll_up_by((app.diff_page_size / 2).max(1));
+                }
+                KeyCode::Char('g') | KeyCode::Home
+                    if matches!(app.current_panel, CurrentPanel::Diff) =>
+                {
+                    app.diff_state.select_first();
+                }
+                KeyCode::Char('G') | KeyCode::End
+                    if matches!(app.current_panel, CurrentPanel::Diff)
+                        && !app.diff.rows.is_empty() =>
+                {
+                    app.diff_state.select(Some(app.diff.rows.len() - 1));
+                }
+                KeyCode::Char('[') | KeyCode::Char(']')
+                    if matches!(
+                        app.current_panel,
+                        CurrentPanel::Branch | CurrentPanel::Remote | CurrentPanel::Tag
+                    ) =>
+                {
+                    let tabs = [BranchTab::Local, BranchTab::Remotes, BranchTab::Tags];
+                    let idx = tabs.iter().position(|t| *t == app.branch_tab).unwrap_or(0);
+                    let step = if key.code == KeyCode::Char(']') {
+                        1
+                    } else {
+                        tabs.len() - 1
+                    };
+                    app.branch_tab = tabs[(idx + step) % tabs.len()];
+                    app.current_panel = crate::state::branch_tab_panel(app.branch_tab);
+                    refresh_scopes(app, &[RefreshScope::Diff]);
+                }
+                KeyCode::Char('T') => {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && let Some(idx) = app.commit_state.selected()
+                        && let Some(commit) = app.commits.get(idx)
+                    {
+                        app.pending_tag_hash = Some(commit.hash.clone());
+                        app.tag_name_input.clear();
+                        app.tag_message_input.clear();
+                        app.tag_popup_message_focus = false;
+                        app.tag_popup_open = true;
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('d')
+                    if matches!(app.current_panel, CurrentPanel::Tag)
+                        && app.tag_state.selected().is_some()
+                        && !app.tags.is_empty() =>
+                {
+                    app.tag_delete_remotes = crate::git_branch::load_remotes();
+                    app.tag_delete_state.select(Some(0));
+                    app.tag_delete_popup_open = true;
+                    return Ok(false);
+                }
+                KeyCode::Char('d') if matches!(app.current_panel, CurrentPanel::Branch) => {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                    {
+                        if branch.is_current {
+                            app.conflict_popup_open = true;
+                            app.conflict_message =
+                                "不能删除当前分支。\n请先切换到其他分支".to_owned();
+                            return Ok(false);
+                        }
+                        let name = crate::git_branch::normalize_branch_name(&branch.name);
+                        app.branch_delete_unmerged = !crate::git_branch::is_merged(&name);
+                        app.branch_delete_upstream = crate::git_branch::upstream_of(&name)
+                            .and_then(|(remote, branch)| {
+                                app.remote_branches
+                                    .iter()
+                                    .find(|r| r.remote == remote && r.branch == branch)
+                                    .cloned()
+                            });
+                        app.branch_delete_state.select(Some(0));
+                        app.branch_delete_popup_open = true;
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('d')
+                    if matches!(app.current_panel, CurrentPanel::Remote)
+                        && app.remote_branch_state.selected().is_some()
+                        && !app.remote_branches.is_empty() =>
+                {
+                    app.remote_delete_popup_open = true;
+                    return Ok(false);
+                }
+                KeyCode::Enter if matches!(app.current_panel, CurrentPanel::Remote) => {
+                    if let Some(idx) = app.remote_branch_state.selected()
+                        && let Some(branch) = app.remote_branches.get(idx)
+                    {
+                        let name = branch.name.clone();
+                        app.current_panel = CurrentPanel::Commit;
+                        crate::state::browse_commits(app, Some(name));
+                    }
+                }
+                KeyCode::Enter if matches!(app.current_panel, CurrentPanel::Branch) => {
+                    if let Some(idx) = app.branch_state.selected()
+                        && let Some(branch) = app.branches.get(idx)
+                    {
+                        // the current branch is HEAD's log
+                        let branch = (!branch.is_current)
+                            .then(|| crate::git_branch::normalize_branch_name(&branch.name));
+                        app.current_panel = CurrentPanel::Commit;
+                        crate::state::browse_commits(app, branch);
+                    }
+                }
+                KeyCode::Char('/') if matches!(app.current_panel, CurrentPanel::Commit) => {
+                    app.commit_filter_open = true;
+                    return Ok(false);
+                }
+                KeyCode::Esc
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && app.commit_detail.is_none()
+                        && (app.commit_filter.is_some()
+                            || app.commits_branch.is_some()
+                            || !app.cherry_pick_clipboard.is_empty()) =>
+                {
+                    if app.commit_filter.is_some() {
+                        app.commit_filter_input.clear();
+                        crate::state::filter_commits(app, None);
+                    } else if app.commits_branch.is_some() {
+                        crate::state::browse_commits(app, None);
+                    } else {
+                        app.cherry_pick_clipboard.clear();
+                    }
+                }
+                KeyCode::Char('t') => {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && let Some(idx) = app.commit_state.selected()
+                        && let Some(commit) = app.commits.get(idx)
+                    {
+                        if app.operation.is_some() {
+                            app.conflict_popup_open = true;
+                            app.conflict_message =
+                                "已有未完成的操作。\n请先按 m 继续或中止".to_owned();
+                            return Ok(false);
+                        }
+                        let hash = commit.hash.clone();
+                        if commit.parents.len() > 1 {
+                            app.revert_popup_open = true;
+                            app.revert_state.select(Some(0));
+                            app.pending_revert_hash = Some(hash);
+                        } else {
+                            revert(app, &hash, None);
+                        }
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('F') => {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && crate::state::is_head_log(app)
+                        && let Some(idx) = app.commit_state.selected()
+                        && let Some(commit) = app.commits.get(idx)
+                    {
+                        let refusal = if app.operation.is_some() {
+                            Some("已有未完成的操作。\n请先按 m 继续或中止")
+                        } else if !crate::git_status::has_staged_changes() {
+                            Some("没有已暂存的改动可用于 fixup")
+                        } else if commit.parents.len() > 1 {
+                            Some("不能 fixup 合并提交")
+                        } else if crate::git_commits::is_on_upstream(&commit.hash) {
+                            Some("该提交已在上游分支中。\nFixup 会改写已推送的历史")
+                        } else {
+                            None
+                        };
+                        if let Some(refusal) = refusal {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = refusal.to_owned();
+                            return Ok(false);
+                        }
+                        let result = crate::git_commits::fixup_commit(&app.commits, idx);
+                        refresh_all_states(app);
+                        if !report_stopped_operation(app)
+                            && let Err(e) = result
+                        {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = format!("Fixup 失败。\n{}", e);
+                        }
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('C') => {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && app.commit_detail.is_none()
+                        && let Some(idx) = app.commit_state.selected()
+                        && let Some(commit) = app.commits.get(idx)
+                    {
+                        if commit.parents.len() > 1 {
+                            app.conflict_popup_open = true;
+                            app.conflict_message = "不能 cherry-pick 合并提交".to_owned();
+                            return Ok(false);
+                        }
+                        let clipboard = &mut app.cherry_pick_clipboard;
+                        match clipboard.iter().position(|c| c.hash == commit.hash) {
+                            Some(pos) => {
+                                clipboard.remove(pos);
+                            }
+                            None => clipboard.push(commit.clone()),
+                        }
+                        app.commit_state.scroll_down_by(1);
+                        refresh_scopes(app, &[RefreshScope::Diff]);
+                    }
+                }
+                KeyCode::Char('V')
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && !app.cherry_pick_clipboard.is_empty() =>
+                {
+                    if app.operation.is_some() {
+                        app.conflict_popup_open = true;
+                        app.conflict_message = "已有未完成的操作。\n请先按 m 继续或中止".to_owned();
+                        return Ok(false);
+                    }
+                    let picks = std::mem::take(&mut app.cherry_pick_clipboard);
+                    let hashes: Vec<String> = crate::git_commits::oldest_first(picks)
+                        .into_iter()
+                        .map(|c| c.hash)
+                        .collect();
+                    let result = crate::git_commits::cherry_pick(&hashes);
+                    app.commits_branch = None;
+                    app.commit_detail = None;
+                    refresh_all_states(app);
+                    if !report_stopped_operation(app)
+                        && let Err(e) = result
+                    {
+                        app.conflict_popup_open = true;
+                        app.conflict_message = format!("Cherry-pick 失败。\n{}", e);
+                    }
+                    return Ok(false);
+                }
+                KeyCode::Enter if matches!(app.current_panel, CurrentPanel::Commit) => {
+                    if app.commit_detail.is_some() {
+                        // inspect the selected file's diff
+                        app.current_panel = CurrentPanel::Diff;
+                    } else {
+                        crate::state::open_commit_detail(app);
+                        refresh_scopes(app, &[RefreshScope::Diff]);
+                    }
+                }
+                KeyCode::Esc
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && app.commit_detail.is_some() =>
+                {
+                    app.commit_detail = None;
+                    refresh_scopes(app, &[RefreshScope::Diff]);
+                }
+                KeyCode::Char('z') | KeyCode::Enter
+                    if matches!(app.current_panel, CurrentPanel::Diff) =>
+                {
+                    if let Some(row) = app.diff_state.selected() {
+                        app.diff_visual_anchor = None;
+                        let idx = crate::git_diff::toggle_fold(
+                            &mut app.diff,
+                            &mut app.diff_folded,
+                            row,
+                            app.diff_split,
+                        );
+                        if let Some(search) = &mut app.diff_search {
+                            crate::git_diff::update_search_hits(&app.diff, search);
+                        }
+                        if idx.is_some() {
+                            app.diff_state.select(idx);
+                        }
+                    }
+                }
+                KeyCode::Char('Z') if matches!(app.current_panel, CurrentPanel::Diff) => {
+                    app.diff_visual_anchor = None;
+                    let file = app
+                        .diff_state
+                        .selected()
+                        .and_then(|idx| crate::git_diff::file_at(&app.diff, idx));
+                    crate::git_diff::toggle_fold_all(
+                        &mut app.diff,
+                        &mut app.diff_folded,
+                        app.diff_split,
+                    );
+                    if let Some(search) = &mut app.diff_search {
+                        crate::git_diff::update_search_hits(&app.diff, search);
+                    }
+                    app.diff_state.select(file.and_then(|file| {
+                        crate::git_diff::find_row(
+                            &app.diff.rows,
+                            crate::state::DiffRow::FileHeader { file, line: 0 },
+                        )
+                    }));
+                }
+                KeyCode::Char('i') => {
+                    if matches!(app.current_panel, CurrentPanel::Commit)
+                        && app.commit_detail.is_none()
+                        && crate::state::is_head_log(app)
+                        && let Some(idx) = app.commit_state.selected()
+                    {
+                        if app.operation.is_some() {
+                            app.conflict_popup_open = true;
+                            app.conflict_message =
+                                "已有未完成的 rebase。\n请先按 m 继续或中止".to_owned();
+                            return Ok(false);
+                        }
+                        match crate::git_rebase::plan_for(&app.commits, idx) {
+                            Ok(plan) => {
+                                app.rebase_state.select(Some(plan.entries.len() - 1));
+                                app.rebase_plan = Some(plan);
+                                refresh_scopes(app, &[RefreshScope::Diff]);
+                            }
+                            Err(e) => {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("Rebase 失败。\n{}", e);
+                            }
+                        }
+                    }
+                }
+                KeyCode::Char('m') if app.operation.is_some() => {
+                    app.operation_popup_open = true;
+                    app.operation_state.select(Some(0));
+                    return Ok(false);
+                }
                 KeyCode::Char('d') => {
                     if matches!(app.current_panel, CurrentPanel::Commit)
+                        && crate::state::is_head_log(app)
                         && let Some(idx) = app.commit_state.selected()
-                        && let Some(line) = app.commits.get(idx)
-                        && let Some(hash) = crate::git_commits::parse_commit_hash(line)
+                        && let Some(commit) = app.commits.get(idx)
                     {
+                        let hash = commit.hash.clone();
                         let _ = crate::git_commits::drop_commit(&hash);
                         refresh_scopes(
                             app,
@@ -220,27 +1236,50 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     }
                 }
 
-                KeyCode::Char('j') | KeyCode::Down => match app.current_panel {
-                    CurrentPanel::Status => app.status_state.scroll_down_by(1),
-                    CurrentPanel::Branch => app.branch_state.scroll_down_by(1),
-                    CurrentPanel::Commit => app.commit_state.scroll_down_by(1),
-                    CurrentPanel::Diff => app.diff_state.scroll_down_by(1),
-                },
-                KeyCode::Char('k') | KeyCode::Up => match app.current_panel {
-                    CurrentPanel::Status => app.status_state.scroll_up_by(1),
-                    CurrentPanel::Branch => app.branch_state.scroll_up_by(1),
-                    CurrentPanel::Commit => app.commit_state.scroll_up_by(1),
-                    CurrentPanel::Diff => app.diff_state.scroll_up_by(1),
-                },
+                KeyCode::Char('j') | KeyCode::Down => {
+                    match app.current_panel {
+                        CurrentPanel::Status => app.status_state.scroll_down_by(1),
+                        CurrentPanel::Branch => app.branch_state.scroll_down_by(1),
+                        CurrentPanel::Remote => app.remote_branch_state.scroll_down_by(1),
+                        CurrentPanel::Tag => app.tag_state.scroll_down_by(1),
+                        CurrentPanel::Commit if app.commit_detail.is_some() => {
+                            app.commit_detail_state.scroll_down_by(1)
+                        }
+                        CurrentPanel::Commit => app.commit_state.scroll_down_by(1),
+                        CurrentPanel::Diff => app.diff_state.scroll_down_by(1),
+                    }
+                    if !matches!(app.current_panel, CurrentPanel::Diff) {
+                        refresh_scopes(app, &[RefreshScope::Diff]);
+                    }
+                }
+                KeyCode::Char('k') | KeyCode::Up => {
+                    match app.current_panel {
+                        CurrentPanel::Status => app.status_state.scroll_up_by(1),
+                        CurrentPanel::Branch => app.branch_state.scroll_up_by(1),
+                        CurrentPanel::Remote => app.remote_branch_state.scroll_up_by(1),
+                        CurrentPanel::Tag => app.tag_state.scroll_up_by(1),
+                        CurrentPanel::Commit if app.commit_detail.is_some() => {
+                            app.commit_detail_state.scroll_up_by(1)
+                        }
+                        CurrentPanel::Commit => app.commit_state.scroll_up_by(1),
+                        CurrentPanel::Diff => app.diff_state.scroll_up_by(1),
+                    }
+                    if !matches!(app.current_panel, CurrentPanel::Diff) {
+                        refresh_scopes(app, &[RefreshScope::Diff]);
+                    }
+                }
 
                 KeyCode::Char('1') => {
                     app.current_panel = CurrentPanel::Status;
+                    refresh_scopes(app, &[RefreshScope::Diff]);
                 }
                 KeyCode::Char('2') => {
-                    app.current_panel = CurrentPanel::Branch;
+                    app.current_panel = crate::state::branch_tab_panel(app.branch_tab);
+                    refresh_scopes(app, &[RefreshScope::Diff]);
                 }
                 KeyCode::Char('3') => {
                     app.current_panel = CurrentPanel::Commit;
+                    refresh_scopes(app, &[RefreshScope::Diff]);
                 }
                 KeyCode::Char('4') => {
                     app.current_panel = CurrentPanel::Diff;
@@ -253,11 +1292,7 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     }
                 }
                 KeyCode::Char('M') => {
-                    if matches!(app.current_panel, CurrentPanel::Branch)
-                        && let Some(idx) = app.branch_state.selected()
-                        && let Some(branch) = app.branches.get(idx)
-                    {
-                        let target = crate::git_branch::normalize_branch_name(&branch.name);
+                    if let Some(target) = selected_branch_ref(app) {
                         let _ = crate::git_branch::merge_branch(&target);
                         if crate::git_branch::has_conflicts().unwrap_or(false) {
                             app.conflict_popup_open = true;
@@ -277,11 +1312,7 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     }
                 }
                 KeyCode::Char('r') => {
-                    if matches!(app.current_panel, CurrentPanel::Branch)
-                        && let Some(idx) = app.branch_state.selected()
-                        && let Some(branch) = app.branches.get(idx)
-                    {
-                        let target = crate::git_branch::normalize_branch_name(&branch.name);
+                    if let Some(target) = selected_branch_ref(app) {
                         let _ = crate::git_branch::rebase_onto_branch(&target);
                         if crate::git_branch::has_conflicts().unwrap_or(false) {
                             app.conflict_popup_open = true;
@@ -302,6 +1333,44 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     }
                 }
                 KeyCode::Char(' ') => match app.current_panel {
+                    CurrentPanel::Remote => {
+                        if let Some(idx) = app.remote_branch_state.selected()
+                            && let Some(branch) = app.remote_branches.get(idx)
+                        {
+                            if let Err(e) = crate::git_branch::checkout_remote_branch(branch) {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("Checkout 失败。\n{}", e);
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Branches,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Status,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                    }
+                    CurrentPanel::Tag => {
+                        if let Some(idx) = app.tag_state.selected()
+                            && let Some(tag) = app.tags.get(idx)
+                        {
+                            if let Err(e) = crate::git_tag::checkout_tag(&tag.name) {
+                                app.conflict_popup_open = true;
+                                app.conflict_message = format!("Checkout tag 失败。\n{}", e);
+                            }
+                            refresh_scopes(
+                                app,
+                                &[
+                                    RefreshScope::Branches,
+                                    RefreshScope::Commits,
+                                    RefreshScope::Status,
+                                    RefreshScope::Diff,
+                                ],
+                            );
+                        }
+                    }
                     CurrentPanel::Branch => {
                         if let Some(idx) = app.branch_state.selected()
                             && let Some(branch) = app.branches.get(idx)
@@ -333,9 +1402,9 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                     }
                     CurrentPanel::Commit => {
                         if let Some(idx) = app.commit_state.selected()
-                            && let Some(line) = app.commits.get(idx)
-                            && let Some(hash) = crate::git_commits::parse_commit_hash(line)
+                            && let Some(commit) = app.commits.get(idx)
                         {
+                            let hash = commit.hash.clone();
                             let _ = crate::git_commits::checkout_commit(&hash);
                             refresh_scopes(
                                 app,
@@ -347,8 +1416,66 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                             );
                         }
                     }
-                    _ => {}
+                    // patches generated with -w do not apply to the real file contents
+                    CurrentPanel::Diff if app.diff_options.ignore_whitespace => {}
+                    CurrentPanel::Diff => {
+                        if let Some(row) = app.diff_state.selected()
+                            && let Some(anchor) = app.diff_visual_anchor
+                        {
+                            let _ = crate::git_diff::toggle_range(&app.diff, anchor, row);
+                            refresh_scopes(app, &[RefreshScope::Status, RefreshScope::Diff]);
+                        } else if let Some(row) = app.diff_state.selected()
+                            && let Some((file, hunk)) = crate::git_diff::hunk_at(&app.diff, row)
+                        {
+                            let file = &app.diff.files[file];
+                            let _ = crate::git_diff::toggle_hunk(file, &file.hunks[hunk]);
+                            refresh_scopes(app, &[RefreshScope::Status, RefreshScope::Diff]);
+                        }
+                    }
                 },
+                KeyCode::Char('v') => {
+                    if matches!(app.current_panel, CurrentPanel::Diff) {
+                        app.diff_visual_anchor = match app.diff_visual_anchor {
+                            Some(_) => None,
+                            None => app.diff_state.selected(),
+                        };
+                    }
+                }
+                KeyCode::Esc => {
+                    app.diff_visual_anchor = None;
+                    app.diff_search = None;
+                }
+                KeyCode::Char('o') => {
+                    if matches!(app.current_panel, CurrentPanel::Diff) {
+                        app.diff_options_popup_open = true;
+                        app.diff_options_state.select(Some(0));
+                        return Ok(false);
+                    }
+                }
+                KeyCode::Char('|') => {
+                    if matches!(app.current_panel, CurrentPanel::Diff) {
+                        let current = app
+                            .diff_state
+                            .selected()
+                            .and_then(|idx| app.diff.rows.get(idx).copied());
+                        app.diff_split = !app.diff_split;
+                        app.diff.rows =
+                            crate::git_diff::build_rows(&app.diff.files, app.diff_split);
+                        if let Some(search) = &mut app.diff_search {
+                            crate::git_diff::update_search_hits(&app.diff, search);
+                        }
+                        app.diff_visual_anchor = None;
+                        app.diff_state.select(
+                            current.and_then(|row| crate::git_diff::find_row(&app.diff.rows, row)),
+                        );
+                    }
+                }
+                KeyCode::Char('s') => {
+                    if matches!(app.current_panel, CurrentPanel::Diff) {
+                        app.diff_syntax_highlight = !app.diff_syntax_highlight;
+                        refresh_scopes(app, &[RefreshScope::Diff]);
+                    }
+                }
                 KeyCode::Char('a') => {
                     if matches!(app.current_panel, CurrentPanel::Status) {
                         let has_staged = app
@@ -367,10 +1494,9 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
                 KeyCode::Char('g') => {
                     if matches!(app.current_panel, CurrentPanel::Commit)
                         && let Some(idx) = app.commit_state.selected()
-                        && let Some(line) = app.commits.get(idx)
-                        && let Some(hash) = crate::git_commits::parse_commit_hash(line)
+                        && let Some(commit) = app.commits.get(idx)
                     {
-                        app.pending_reset_hash = Some(hash);
+                        app.pending_reset_hash = Some(commit.hash.clone());
                         app.reset_popup_open = true;
                         app.reset_state.select(Some(1));
                         return Ok(false);
@@ -383,3 +1509,57 @@ pub fn handle_events(app: &mut AppState) -> std::io::Result<bool> {
     }
     Ok(false)
 }
+
+// reverts `hash` without committing and opens the commit popup with git's message
+fn revert(app: &mut AppState, hash: &str, mainline: Option<usize>) {
+    let result = crate::git_commits::revert_commit(hash, mainline);
+    refresh_all_states(app);
+    match result {
+        Ok(()) => {
+            app.commit_popup_open = true;
+            app.commit_input = crate::git_commits::prepared_message();
+            app.commit_popup_mode = CommitPopupMode::New;
+        }
+        Err(e) => {
+            if !report_stopped_operation(app) {
+                app.conflict_popup_open = true;
+                app.conflict_message = format!("Revert 失败。\n{}", e);
+            }
+        }
+    }
+}
+
+// after a rebase, cherry-pick or revert step, tells the user why it stopped; false if nothing is in progress
+fn report_stopped_operation(app: &mut AppState) -> bool {
+    if app.operation.is_none() {
+        return false;
+    }
+    app.conflict_popup_open = true;
+    let name = app.operation.map_or("", crate::git_rebase::operation_name);
+    app.conflict_message = if crate::git_branch::has_conflicts().unwrap_or(false) {
+        format!(
+            "检测到 {} 冲突。\n请手动解决冲突并暂存，然后按 m 继续",
+            name
+        )
+    } else {
+        format!("{} 已暂停。\n修改完成后按 m 继续", name)
+    };
+    true
+}
+
+// merge/rebase target selected in the Branches or Remotes tab
+fn selected_branch_ref(app: &AppState) -> Option<String> {
+    match app.current_panel {
+        CurrentPanel::Branch => app
+            .branch_state
+            .selected()
+            .and_then(|idx| app.branches.get(idx))
+            .map(|b| crate::git_branch::normalize_branch_name(&b.name)),
+        CurrentPanel::Remote => app
+            .remote_branch_state
+            .selected()
+            .and_then(|idx| app.remote_branches.get(idx))
+            .map(|b| b.name.clone()),
+        _ => None,
+    }
+}
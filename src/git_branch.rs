@@ -4,11 +4,12 @@ use std::{
 };
 
 use ratatui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{self, List, ListItem},
 };
 
-use crate::state::BranchInfo;
+use crate::state::{BranchInfo, BranchTab, RemoteBranchInfo};
 
 pub fn load_branches() -> Vec<BranchInfo> {
     let current = Command::new("git")
@@ -35,7 +36,7 @@ pub fn load_branches() -> Vec<BranchInfo> {
     for line in s.lines() {
         let mut parts = line.split('\t');
         let name = parts.next().unwrap_or("").to_string();
-        let _upstream = parts.next().unwrap_or("");
+        let upstream = parts.next().unwrap_or("");
         let track = parts.next().unwrap_or("");
 
         let mut ahead: u32 = 0;
@@ -62,14 +63,39 @@ pub fn load_branches() -> Vec<BranchInfo> {
             ahead,
             behind,
             is_current: !current.is_empty() && current == name,
+            upstream: (!upstream.is_empty()).then(|| upstream.to_string()),
+            upstream_gone: track == "[gone]",
         });
     }
 
     res
 }
 
+// title of the middle left panel, listing its tabs with the active one highlighted
+pub fn tabs_title(active: BranchTab) -> Line<'static> {
+    let tabs = [
+        (BranchTab::Local, "Branches"),
+        (BranchTab::Remotes, "Remotes"),
+        (BranchTab::Tags, "Tags"),
+    ];
+    let mut spans = Vec::new();
+    for (i, (tab, name)) in tabs.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
+        }
+        let style = if tab == active {
+            Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            Style::new().fg(Color::DarkGray)
+        };
+        spans.push(Span::styled(name, style));
+    }
+    spans.push(Span::styled(" ([/]切换)", Style::new().fg(Color::DarkGray)));
+    Line::from(spans)
+}
+
 pub fn widget(branches: &[BranchInfo], focused: bool) -> List<'_> {
-    let block = widgets::Block::bordered().title("Branches");
+    let block = widgets::Block::bordered().title(tabs_title(BranchTab::Local));
 
     let items: Vec<ListItem> = branches
         .iter()
@@ -91,7 +117,95 @@ pub fn widget(branches: &[BranchInfo], focused: bool) -> List<'_> {
                 label.push(' ');
                 label.push_str(&counters.join(" "));
             }
-            ListItem::new(label)
+            let mut spans = vec![Span::raw(label)];
+            match &b.upstream {
+                Some(upstream) if b.upstream_gone => spans.push(Span::styled(
+                    format!(" {} [gone]", upstream),
+                    Style::new().fg(Color::Red),
+                )),
+                Some(upstream) => spans.push(Span::styled(
+                    format!(" → {}", upstream),
+                    Style::new().fg(Color::DarkGray),
+                )),
+                None => {}
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = widgets::List::new(items).block(block);
+    if focused {
+        return list.highlight_style(Style::new().bg(Color::Yellow));
+    }
+    list
+}
+
+// sorted by remote, then branch; `origin/HEAD` style symrefs are left out
+pub fn load_remote_branches() -> Vec<RemoteBranchInfo> {
+    let output = Command::new("git")
+        .args([
+            "for-each-ref",
+            "--sort=refname",
+            "--format=%(refname)%00%(refname:short)%00%(symref)%00%(committerdate:unix)%00%(contents:subject)",
+            "refs/remotes",
+        ])
+        .output()
+        .map(|o| o.stdout)
+        .unwrap_or_default();
+    let remotes = load_remotes();
+
+    String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split('\0').collect();
+            if f.len() < 5 || !f[2].is_empty() {
+                return None;
+            }
+            let path = f[0].strip_prefix("refs/remotes/")?;
+            // remote names may contain '/', match the longest configured one
+            let remote = remotes
+                .iter()
+                .filter(|r| path.starts_with(&format!("{}/", r)))
+                .max_by_key(|r| r.len())
+                .map(|r| r.as_str())
+                .or_else(|| split_remote_ref(path).map(|(remote, _)| remote))?;
+            Some(RemoteBranchInfo {
+                name: f[1].to_string(),
+                remote: remote.to_string(),
+                branch: path[remote.len() + 1..].to_string(),
+                date: f[3].parse().unwrap_or(0),
+                subject: f[4].to_string(),
+            })
+        })
+        .collect()
+}
+
+// remote name is only shown on the first branch of its group
+pub fn remote_widget(branches: &[RemoteBranchInfo], focused: bool) -> List<'_> {
+    let block = widgets::Block::bordered().title(tabs_title(BranchTab::Remotes));
+    let width = branches.iter().map(|b| b.remote.len()).max().unwrap_or(0);
+
+    let items: Vec<ListItem> = branches
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let first = i == 0 || branches[i - 1].remote != b.remote;
+            let remote = if first { b.remote.as_str() } else { "" };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<width$} ", remote, width = width),
+                    Style::new().fg(Color::Red),
+                ),
+                Span::raw(b.branch.as_str()),
+                Span::styled(
+                    format!(
+                        " {} {}",
+                        crate::git_commits::relative_date(b.date),
+                        b.subject
+                    ),
+                    Style::new().fg(Color::DarkGray),
+                ),
+            ]))
         })
         .collect();
 
@@ -102,6 +216,190 @@ pub fn widget(branches: &[BranchInfo], focused: bool) -> List<'_> {
     list
 }
 
+// checks out the local branch of the same name, creating it to track the
+// remote branch if there is none yet
+pub fn checkout_remote_branch(branch: &RemoteBranchInfo) -> io::Result<()> {
+    if local_branch_exists(&branch.branch)? {
+        return checkout_local(&branch.branch);
+    }
+    checkout_tracking(&branch.name)
+}
+
+pub fn delete_remote_branch(branch: &RemoteBranchInfo) -> io::Result<()> {
+    let status = Command::new("git")
+        .args([
+            "push",
+            "--quiet",
+            &branch.remote,
+            "--delete",
+            &branch.branch,
+        ])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "deleting {} on {} failed",
+            branch.branch, branch.remote
+        )))
+    }
+}
+
+// true when `git branch -d` accepts deleting `name`: git checks the branch
+// against its upstream when that still exists, and against HEAD otherwise
+pub fn is_merged(name: &str) -> bool {
+    let upstream = format!("{}@{{upstream}}", name);
+    let has_upstream = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", &upstream])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    Command::new("git")
+        .args(["merge-base", "--is-ancestor"])
+        .arg(format!("refs/heads/{}", name))
+        .arg(if has_upstream {
+            upstream.as_str()
+        } else {
+            "HEAD"
+        })
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+// remote and branch name on the remote `name` is configured to track
+pub fn upstream_of(name: &str) -> Option<(String, String)> {
+    let output = Command::new("git")
+        .args([
+            "for-each-ref",
+            "--format=%(upstream:remotename)%00%(upstream:remoteref)",
+        ])
+        .arg(format!("refs/heads/{}", name))
+        .output()
+        .ok()?;
+    let s = String::from_utf8_lossy(&output.stdout);
+    let (remote, remote_ref) = s.trim_end().split_once('\0')?;
+    let branch = remote_ref.strip_prefix("refs/heads/")?;
+    // `.` is the local repository, not a remote
+    if remote.is_empty() || remote == "." {
+        return None;
+    }
+    Some((remote.to_string(), branch.to_string()))
+}
+
+pub fn set_upstream(name: &str, upstream: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["branch", "--quiet"])
+        .arg(format!("--set-upstream-to={}", upstream))
+        .arg(name)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "setting the upstream of {} to {} failed",
+            name, upstream
+        )))
+    }
+}
+
+pub fn unset_upstream(name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["branch", "--unset-upstream", name])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "git branch --unset-upstream {} failed",
+            name
+        )))
+    }
+}
+
+// first push of a branch, publishes it under the same name and tracks it
+pub fn push_set_upstream(remote: &str, name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["push", "--quiet", "--set-upstream", remote, name])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "git push --set-upstream {} {} failed",
+            remote, name
+        )))
+    }
+}
+
+// fetches the upstream straight into `refs/heads/<name>`; without a leading `+`
+// the refspec only ever fast-forwards, and the working tree is left alone
+pub fn fast_forward_branch(name: &str) -> io::Result<()> {
+    let Some((remote, branch)) = upstream_of(name) else {
+        return Err(io::Error::other(format!("{} has no upstream", name)));
+    };
+    let status = Command::new("git")
+        .args(["fetch", "--quiet", &remote])
+        .arg(format!("refs/heads/{}:refs/heads/{}", branch, name))
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "fast-forwarding {} to {}/{} failed",
+            name, remote, branch
+        )))
+    }
+}
+
+pub fn delete_branch(name: &str, force: bool) -> io::Result<()> {
+    let flag = if force { "-D" } else { "-d" };
+    let status = Command::new("git")
+        .args(["branch", flag, name])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "git branch {} {} failed",
+            flag, name
+        )))
+    }
+}
+
+pub fn rename_branch(old: &str, new: &str) -> io::Result<()> {
+    let new = new.trim();
+    if new.is_empty() {
+        return Err(io::Error::other("branch name cannot be empty"));
+    }
+    let status = Command::new("git")
+        .args(["branch", "-m", old, new])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "git branch -m {} {} failed",
+            old, new
+        )))
+    }
+}
+
 pub fn checkout_branch(raw_name: &str) -> std::io::Result<()> {
     let name = normalize_branch_name(raw_name);
     if name.is_empty() {
@@ -205,6 +503,29 @@ fn local_branch_exists(name: &str) -> io::Result<bool> {
     Ok(status.success())
 }
 
+pub fn load_remotes() -> Vec<String> {
+    Command::new("git")
+        .arg("remote")
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// remote used when none is given: `origin` if it exists, else the first one
+pub fn default_remote() -> Option<String> {
+    let remotes = load_remotes();
+    remotes
+        .iter()
+        .find(|r| *r == "origin")
+        .or(remotes.first())
+        .cloned()
+}
+
 fn find_remote_for_branch(name: &str) -> io::Result<Option<String>> {
     let output = Command::new("git")
         .args(["for-each-ref", "--format=%(refname:short)", "refs/remotes"])
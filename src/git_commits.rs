@@ -1,27 +1,534 @@
+use crate::state::{
+    CommitDetail, CommitFile, CommitFilter, CommitGraph, CommitInfo, GitOperation, GraphCell,
+    RebaseAction,
+};
 use ratatui::{
-    style::{Color, Style},
-    widgets::{self, List, ListItem},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{self, List, ListItem, Paragraph, Wrap},
+};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, ChildStdout, Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
 };
-use std::process::{Command, Stdio};
+use unicode_width::UnicodeWidthChar;
+
+// one field per placeholder, separated by NUL; `-z` terminates each commit with NUL as well
+const LOG_FORMAT: &str =
+    "--format=%H%x00%h%x00%an%x00%ae%x00%at%x00%cn%x00%ce%x00%ct%x00%s%x00%b%x00%P%x00%D";
+const LOG_FIELDS: usize = 12;
+
+const AUTHOR_WIDTH: usize = 12;
+
+// `git log` kept running between pages, so each page continues the revision
+// walk where the previous one stopped instead of starting it over
+pub struct CommitStream {
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CommitStream {
+    // date order lets git print the first commits without walking the whole history
+    pub fn open(rev: &str, filter: Option<&CommitFilter>) -> Option<Self> {
+        let filter = filter.cloned().unwrap_or_default();
+        let mut child = Command::new("git")
+            .args(["log", "-z", "--decorate=short", LOG_FORMAT])
+            .args(filter_args(&filter))
+            .args([rev, "--"])
+            .args((!filter.path.is_empty()).then_some(&filter.path))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let stdout = BufReader::new(child.stdout.take()?);
+        Some(Self { child, stdout })
+    }
+
+    // up to `count` more commits, fewer once the history is exhausted
+    pub fn next_page(&mut self, count: usize) -> Vec<CommitInfo> {
+        let mut buf = Vec::new();
+        // every field, the last one included, ends with NUL
+        for _ in 0..count * LOG_FIELDS {
+            match self.stdout.read_until(0, &mut buf) {
+                Ok(n) if n > 0 => {}
+                _ => break,
+            }
+        }
+        parse_log(&String::from_utf8_lossy(&buf))
+    }
+}
+
+impl Drop for CommitStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn filter_args(filter: &CommitFilter) -> Vec<String> {
+    let mut args = Vec::new();
+    if !filter.message.is_empty() {
+        args.push(format!("--grep={}", filter.message));
+        args.push("--regexp-ignore-case".to_string());
+    }
+    let options = [
+        ("--author=", &filter.author),
+        ("--since=", &filter.since),
+        ("--until=", &filter.until),
+        ("-S", &filter.pickaxe),
+        ("-G", &filter.pickaxe_regex),
+    ];
+    for (flag, value) in options {
+        if !value.is_empty() {
+            args.push(format!("{}{}", flag, value));
+        }
+    }
+    args
+}
+
+// parses the filter prompt: `author:`, `path:`, `since:`, `until:`, `-S:` and `-G:`
+// prefixed words set those fields, everything else is matched against the
+// message; values containing spaces can be double quoted
+pub fn parse_filter(input: &str) -> Option<CommitFilter> {
+    let mut filter = CommitFilter::default();
+    let mut message = Vec::new();
+    for word in split_quoted(input) {
+        let field = match word.split_once(':') {
+            Some(("author", v)) => Some((&mut filter.author, v)),
+            Some(("path", v)) => Some((&mut filter.path, v)),
+            Some(("since", v)) => Some((&mut filter.since, v)),
+            Some(("until", v)) => Some((&mut filter.until, v)),
+            Some(("-S", v)) => Some((&mut filter.pickaxe, v)),
+            Some(("-G", v)) => Some((&mut filter.pickaxe_regex, v)),
+            _ => None,
+        };
+        match field {
+            Some((field, value)) => *field = value.to_string(),
+            None => message.push(word),
+        }
+    }
+    filter.message = message.join(" ");
+    (filter != CommitFilter::default()).then_some(filter)
+}
+
+fn split_quoted(input: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
 
-pub fn load_commits() -> Vec<String> {
+pub fn rev_hash(rev: &str) -> Option<String> {
     let output = Command::new("git")
-        .args(["log", "--oneline"])
+        .args(["rev-parse", "--verify", "-q", rev])
         .output()
-        .expect("failed to execute git status command.")
-        .stdout;
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
 
-    String::from_utf8(output)
-        .unwrap_or_default()
-        .lines()
-        .map(|s| s.to_string())
+// ref decorations of every ref tip, keyed by commit hash; lets already loaded
+// commits pick up new or deleted refs without reloading the history
+pub fn load_ref_labels() -> HashMap<String, Vec<String>> {
+    let output = Command::new("git")
+        .args([
+            "log",
+            "-z",
+            "--no-walk",
+            "--decorate=short",
+            "--format=%H%x00%D",
+            "--branches",
+            "--tags",
+            "--remotes",
+            "HEAD",
+        ])
+        .output()
+        .map(|o| o.stdout)
+        .unwrap_or_default();
+
+    let s = String::from_utf8_lossy(&output);
+    let fields: Vec<&str> = s.split('\0').collect();
+    fields
+        .chunks_exact(2)
+        .map(|f| (f[0].to_string(), parse_refs(f[1])))
         .collect()
 }
 
-pub fn widget(commits: &[String], focused: bool) -> List<'_> {
-    let block = widgets::Block::bordered().title("Commits");
+fn parse_refs(s: &str) -> Vec<String> {
+    s.split(", ")
+        .filter(|r| !r.is_empty())
+        .map(|r| r.to_string())
+        .collect()
+}
+
+// files changed by `hash` compared to its first parent, with line counts
+pub fn load_commit_files(hash: &str) -> Vec<CommitFile> {
+    let output = Command::new("git")
+        .args([
+            "show",
+            "--format=",
+            "--diff-merges=first-parent",
+            "--find-renames",
+            "-z",
+            "--raw",
+            "--numstat",
+            hash,
+        ])
+        .output()
+        .map(|o| o.stdout)
+        .unwrap_or_default();
 
-    let items: Vec<ListItem> = commits.iter().map(|s| ListItem::new(s.as_str())).collect();
+    parse_commit_files(&String::from_utf8_lossy(&output))
+}
+
+// `--raw` entries come first (":modes hashes status\0path\0[new path\0]"), followed
+// by one `--numstat` entry per file in the same order ("add\tdel\tpath\0", or
+// "add\tdel\t\0old\0new\0" for renames and copies)
+fn parse_commit_files(s: &str) -> Vec<CommitFile> {
+    let mut tokens = s.split('\0');
+    let mut files = Vec::new();
+    let mut counts = Vec::new();
+    while let Some(token) = tokens.next() {
+        if let Some(raw) = token.strip_prefix(':') {
+            let status = raw
+                .rsplit(' ')
+                .next()
+                .and_then(|s| s.chars().next())
+                .unwrap_or('M');
+            let first = tokens.next().unwrap_or_default().to_string();
+            let file = if matches!(status, 'R' | 'C') {
+                CommitFile {
+                    status,
+                    path: tokens.next().unwrap_or_default().to_string(),
+                    old_path: Some(first),
+                    additions: None,
+                    deletions: None,
+                }
+            } else {
+                CommitFile {
+                    status,
+                    path: first,
+                    old_path: None,
+                    additions: None,
+                    deletions: None,
+                }
+            };
+            files.push(file);
+        } else if let Some((add, rest)) = token.split_once('\t')
+            && let Some((del, path)) = rest.split_once('\t')
+        {
+            if path.is_empty() {
+                // rename or copy, skip the two paths
+                tokens.next();
+                tokens.next();
+            }
+            // binary files report "-" for both counts
+            counts.push((add.parse().ok(), del.parse().ok()));
+        }
+    }
+    for (file, (add, del)) in files.iter_mut().zip(counts) {
+        file.additions = add;
+        file.deletions = del;
+    }
+    files
+}
+
+fn parse_log(s: &str) -> Vec<CommitInfo> {
+    let fields: Vec<&str> = s.split('\0').collect();
+    fields
+        .chunks_exact(LOG_FIELDS)
+        .map(|f| CommitInfo {
+            hash: f[0].trim_start_matches('\n').to_string(),
+            short_hash: f[1].to_string(),
+            author_name: f[2].to_string(),
+            author_email: f[3].to_string(),
+            author_date: f[4].parse().unwrap_or(0),
+            committer_name: f[5].to_string(),
+            committer_email: f[6].to_string(),
+            committer_date: f[7].parse().unwrap_or(0),
+            subject: f[8].to_string(),
+            body: f[9].trim_end().to_string(),
+            parents: f[10].split_whitespace().map(|p| p.to_string()).collect(),
+            refs: parse_refs(f[11]),
+        })
+        .collect()
+}
+
+// "5m", "3h", "2d", "6w", "4mo", "2y"
+pub fn relative_date(timestamp: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(timestamp);
+    let secs = (now - timestamp).max(0);
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s if s < 86400 * 14 => format!("{}d", s / 86400),
+        s if s < 86400 * 60 => format!("{}w", s / (86400 * 7)),
+        s if s < 86400 * 365 => format!("{}mo", s / (86400 * 30)),
+        s => format!("{}y", s / (86400 * 365)),
+    }
+}
+
+// truncates or pads `s` to exactly `width` terminal columns
+fn fit(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
+}
+
+fn ref_style(r: &str) -> Style {
+    if r.starts_with("HEAD") {
+        Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+    } else if r.starts_with("tag: ") {
+        Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+    } else if r.contains('/') {
+        Style::new().fg(Color::Red)
+    } else {
+        Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)
+    }
+}
+
+const LANE_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+fn lane_color(lane: usize) -> Color {
+    LANE_COLORS[lane % LANE_COLORS.len()]
+}
+
+// appends one graph row per commit, continuing from the lanes left by earlier rows
+pub fn extend_graph(graph: &mut CommitGraph, commits: &[CommitInfo]) {
+    for c in commits {
+        let row = graph_row(&mut graph.lanes, c);
+        graph.rows.push(row);
+    }
+}
+
+// first free lane not in `skip`
+fn free_lane(lanes: &mut Vec<Option<String>>, skip: &[usize]) -> usize {
+    match (0..lanes.len()).position(|i| lanes[i].is_none() && !skip.contains(&i)) {
+        Some(i) => i,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+// every lane takes two columns: the lane symbol and a connector to its right
+fn graph_row(lanes: &mut Vec<Option<String>>, c: &CommitInfo) -> Vec<GraphCell> {
+    let waiting = |l: &Option<String>| l.as_deref() == Some(c.hash.as_str());
+    // a commit nobody is waiting for is a branch tip and opens a new lane
+    let col = match lanes.iter().position(waiting) {
+        Some(i) => i,
+        None => free_lane(lanes, &[]),
+    };
+    let before: Vec<bool> = lanes.iter().map(|l| l.is_some()).collect();
+
+    // lanes that were also waiting for this commit end here
+    let joins: Vec<usize> = (col + 1..lanes.len())
+        .filter(|&i| waiting(&lanes[i]))
+        .collect();
+    for &i in &joins {
+        lanes[i] = None;
+    }
+
+    lanes[col] = c.parents.first().cloned();
+    // merge parents either join a lane already waiting for them or fork a new one
+    let mut forks = Vec::new();
+    for p in c.parents.iter().skip(1) {
+        match lanes.iter().position(|l| l.as_deref() == Some(p.as_str())) {
+            Some(i) => forks.push((i, true)),
+            // a lane joining on this row still needs its column for the `┘`
+            None => {
+                let i = free_lane(lanes, &joins);
+                lanes[i] = Some(p.clone());
+                forks.push((i, false));
+            }
+        }
+    }
+
+    let width = lanes.len().max(before.len());
+    let mut cells: Vec<GraphCell> = (0..width * 2)
+        .map(|i| GraphCell {
+            symbol: if i % 2 == 0 && before.get(i / 2) == Some(&true) {
+                '│'
+            } else {
+                ' '
+            },
+            lane: i / 2,
+        })
+        .collect();
+
+    let ends = joins
+        .iter()
+        .map(|&i| (i, if i > col { '┘' } else { '└' }))
+        .chain(forks.iter().map(|&(i, existing)| {
+            let symbol = match (i > col, existing) {
+                (true, true) => '┤',
+                (false, true) => '├',
+                (true, false) => '┐',
+                (false, false) => '┌',
+            };
+            (i, symbol)
+        }));
+    for (end, symbol) in ends {
+        let (from, to) = (col.min(end) * 2, col.max(end) * 2);
+        for cell in &mut cells[from + 1..to] {
+            cell.symbol = match cell.symbol {
+                '│' => '┼',
+                ' ' => '─',
+                s => s,
+            };
+            cell.lane = end;
+        }
+        cells[end * 2] = GraphCell { symbol, lane: end };
+    }
+    cells[col * 2] = GraphCell {
+        symbol: if c.parents.len() > 1 { '◎' } else { '●' },
+        lane: col,
+    };
+
+    while lanes.last() == Some(&None) {
+        lanes.pop();
+    }
+    let used = width.min(
+        lanes
+            .len()
+            .max(col + 1)
+            .max(joins.last().map_or(0, |j| j + 1)),
+    );
+    // the last lane needs no connector
+    cells.truncate(used * 2 - 1);
+    cells
+}
+
+fn graph_spans(row: &[GraphCell]) -> Vec<Span<'static>> {
+    row.iter()
+        .map(|cell| {
+            Span::styled(
+                cell.symbol.to_string(),
+                Style::new().fg(lane_color(cell.lane)),
+            )
+        })
+        .collect()
+}
+
+pub fn commit_line<'a>(c: &'a CommitInfo, graph: &[GraphCell], copied: bool) -> Line<'a> {
+    // commits copied for cherry-picking are marked on their hash
+    let hash_style = if copied {
+        Style::new().fg(Color::Black).bg(Color::Cyan)
+    } else {
+        Style::new().fg(Color::Yellow)
+    };
+    let mut spans = vec![
+        Span::styled(c.short_hash.as_str(), hash_style),
+        Span::raw(" "),
+        Span::styled(
+            format!("{:>4} ", relative_date(c.author_date)),
+            Style::new().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!("{} ", fit(&c.author_name, AUTHOR_WIDTH)),
+            Style::new().fg(Color::Blue),
+        ),
+    ];
+    spans.extend(graph_spans(graph));
+    spans.push(Span::raw(" "));
+    for r in &c.refs {
+        spans.push(Span::styled(format!("({}) ", r), ref_style(r)));
+    }
+    spans.push(Span::raw(c.subject.as_str()));
+    Line::from(spans)
+}
+
+// "Commits", plus the branch being browsed, the filter, copied commits and a
+// stopped operation
+pub fn title(
+    branch: Option<&str>,
+    filter: Option<&str>,
+    copied: usize,
+    operation: Option<GitOperation>,
+) -> String {
+    let mut title = String::from("Commits");
+    if let Some(branch) = branch {
+        title.push_str(&format!(" - {} (Esc返回)", branch));
+    }
+    if let Some(filter) = filter {
+        title.push_str(&format!(" [filter: {}, Esc清除]", filter));
+    }
+    if copied > 0 {
+        title.push_str(&format!(" [{} copied, V粘贴]", copied));
+    }
+    if let Some(op) = operation {
+        title.push_str(&format!(
+            " [{} in progress, m继续/跳过/中止]",
+            crate::git_rebase::operation_name(op)
+        ));
+    }
+    title
+}
+
+pub fn widget<'a>(
+    commits: &'a [CommitInfo],
+    graph: &CommitGraph,
+    loading: bool,
+    copied: &[CommitInfo],
+    title: String,
+    focused: bool,
+) -> List<'a> {
+    let block = widgets::Block::bordered().title(title);
+
+    let items: Vec<ListItem> = commits
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let row = graph.rows.get(i).map_or(&[][..], |r| r.as_slice());
+            let is_copied = copied.iter().any(|p| p.hash == c.hash);
+            ListItem::new(commit_line(c, row, is_copied))
+        })
+        .chain(loading.then(|| {
+            ListItem::new(Span::styled(
+                "loading more...",
+                Style::new().fg(Color::DarkGray),
+            ))
+        }))
+        .collect();
     let list = widgets::List::new(items).block(block);
     if focused {
         return list.highlight_style(Style::new().bg(Color::Yellow));
@@ -29,6 +536,114 @@ pub fn widget(commits: &[String], focused: bool) -> List<'_> {
     list
 }
 
+pub fn detail_widget(detail: &CommitDetail, focused: bool) -> List<'_> {
+    let block = widgets::Block::bordered().title(format!(
+        "Commit {} - {} files (Esc返回)",
+        detail.commit.short_hash,
+        detail.files.len()
+    ));
+
+    let items: Vec<ListItem> = detail
+        .files
+        .iter()
+        .map(|f| {
+            let status_color = match f.status {
+                'A' => Color::Green,
+                'D' => Color::Red,
+                'R' | 'C' => Color::Cyan,
+                _ => Color::Yellow,
+            };
+            let mut spans = vec![Span::styled(
+                format!("{} ", f.status),
+                Style::new().fg(status_color),
+            )];
+            match (f.additions, f.deletions) {
+                (Some(add), Some(del)) => {
+                    spans.push(Span::styled(
+                        format!("+{:<4}", add),
+                        Style::new().fg(Color::Green),
+                    ));
+                    spans.push(Span::styled(
+                        format!("-{:<4} ", del),
+                        Style::new().fg(Color::Red),
+                    ));
+                }
+                _ => spans.push(Span::styled(
+                    format!("{:<11}", "bin"),
+                    Style::new().fg(Color::DarkGray),
+                )),
+            }
+            match &f.old_path {
+                Some(old) => spans.push(Span::raw(format!("{} → {}", old, f.path))),
+                None => spans.push(Span::raw(f.path.as_str())),
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = widgets::List::new(items).block(block);
+    if focused {
+        return list.highlight_style(Style::new().bg(Color::Yellow));
+    }
+    list
+}
+
+// full message and metadata of the commit shown in the detail view
+pub fn detail_lines(c: &CommitInfo) -> Vec<Line<'_>> {
+    let label = Style::new().fg(Color::DarkGray);
+    let mut lines = vec![Line::from(vec![
+        Span::styled("commit    ", label),
+        Span::styled(c.hash.as_str(), Style::new().fg(Color::Yellow)),
+    ])];
+    if !c.refs.is_empty() {
+        let mut spans = vec![Span::styled("refs      ", label)];
+        for r in &c.refs {
+            spans.push(Span::styled(format!("({}) ", r), ref_style(r)));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(vec![
+        Span::styled("author    ", label),
+        Span::raw(format!(
+            "{} <{}>, {} ago",
+            c.author_name,
+            c.author_email,
+            relative_date(c.author_date)
+        )),
+    ]));
+    lines.push(Line::from(vec![
+        Span::styled("committer ", label),
+        Span::raw(format!(
+            "{} <{}>, {} ago",
+            c.committer_name,
+            c.committer_email,
+            relative_date(c.committer_date)
+        )),
+    ]));
+    if !c.parents.is_empty() {
+        let parents: Vec<&str> = c.parents.iter().map(|p| &p[..p.len().min(7)]).collect();
+        lines.push(Line::from(vec![
+            Span::styled("parents   ", label),
+            Span::styled(parents.join(" "), Style::new().fg(Color::Yellow)),
+        ]));
+    }
+    lines.push(Line::default());
+    lines.push(Line::styled(
+        c.subject.as_str(),
+        Style::new().add_modifier(Modifier::BOLD),
+    ));
+    if !c.body.is_empty() {
+        lines.push(Line::default());
+        lines.extend(c.body.lines().map(Line::raw));
+    }
+    lines
+}
+
+pub fn detail_info(lines: Vec<Line<'_>>) -> Paragraph<'_> {
+    Paragraph::new(lines)
+        .block(widgets::Block::bordered().title("Commit info"))
+        .wrap(Wrap { trim: false })
+}
+
 pub fn commit(message: &str) -> std::io::Result<()> {
     if message.trim().is_empty() {
         return Err(std::io::Error::other("commit message cannot be empty"));
@@ -55,14 +670,9 @@ pub fn commit(message: &str) -> std::io::Result<()> {
     }
 }
 
-pub fn parse_commit_hash(line: &str) -> Option<String> {
-    let mut parts = line.split_whitespace();
-    parts.next().map(|s| s.to_string())
-}
-
-pub fn get_head_commit_message() -> std::io::Result<String> {
+pub fn get_commit_message(hash: &str) -> std::io::Result<String> {
     let output = Command::new("git")
-        .args(["log", "-1", "--format=%B"])
+        .args(["log", "-1", "--format=%B", hash])
         .output()?;
     if output.status.success() {
         Ok(String::from_utf8(output.stdout).unwrap_or_default())
@@ -71,6 +681,16 @@ pub fn get_head_commit_message() -> std::io::Result<String> {
     }
 }
 
+// true when `hash` is reachable from the current branch's upstream, i.e. already pushed
+pub fn is_on_upstream(hash: &str) -> bool {
+    Command::new("git")
+        .args(["merge-base", "--is-ancestor", hash, "@{upstream}"])
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub fn amend_last_no_edit() -> std::io::Result<()> {
     let status = Command::new("git")
         .args(["commit", "--amend", "--no-edit", "-q"])
@@ -154,6 +774,131 @@ pub fn reword_last_commit(message: &str) -> std::io::Result<()> {
     }
 }
 
+// rewords any commit of the loaded history; older commits are rewritten by
+// replaying everything above them
+pub fn reword_commit(commits: &[CommitInfo], hash: &str, message: &str) -> std::io::Result<()> {
+    if message.trim().is_empty() {
+        return Err(std::io::Error::other("commit message cannot be empty"));
+    }
+    let Some(idx) = commits.iter().position(|c| c.hash == hash) else {
+        return Err(std::io::Error::other(
+            "commit is not in the current history",
+        ));
+    };
+    if idx == 0 {
+        return reword_last_commit(message);
+    }
+    let mut plan = crate::git_rebase::plan_for(commits, idx)?;
+    plan.entries[idx].action = RebaseAction::Reword;
+    plan.entries[idx].message = Some(message.trim().to_string());
+    crate::git_rebase::run_plan(&plan)
+}
+
+// folds the staged changes into `commits[idx]`: HEAD is amended, older commits
+// get a `fixup!` commit that is autosquashed into place right away
+pub fn fixup_commit(commits: &[CommitInfo], idx: usize) -> std::io::Result<()> {
+    let Some(target) = commits.get(idx) else {
+        return Err(std::io::Error::other("no commit selected"));
+    };
+    if idx == 0 {
+        return amend_last_no_edit();
+    }
+    if commits[..=idx].iter().any(|c| c.parents.len() > 1) {
+        return Err(std::io::Error::other(
+            "a merge commit sits above the selected commit, cannot autosquash",
+        ));
+    }
+
+    let status = Command::new("git")
+        .args(["commit", "-q"])
+        .arg(format!("--fixup={}", target.hash))
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other("git commit --fixup failed"));
+    }
+    crate::git_rebase::autosquash(target.parents.first().map(|p| p.as_str()))
+}
+
+// true when `ancestor` is reachable from `descendant`
+fn is_ancestor(ancestor: &str, descendant: &str) -> bool {
+    Command::new("git")
+        .args(["merge-base", "--is-ancestor", ancestor, descendant])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+// order git should apply `commits` in: every commit after its ancestors, and
+// unrelated commits by committer date; dates alone tie for commits made by the
+// same rebase or cherry-pick
+pub fn oldest_first(mut commits: Vec<CommitInfo>) -> Vec<CommitInfo> {
+    commits.sort_by_key(|c| c.committer_date);
+    let mut ordered: Vec<CommitInfo> = Vec::with_capacity(commits.len());
+    for commit in commits {
+        // ancestors of `commit` are ancestors of its first descendant too, so
+        // they already sit before that position
+        let pos = ordered
+            .iter()
+            .position(|c| is_ancestor(&commit.hash, &c.hash))
+            .unwrap_or(ordered.len());
+        ordered.insert(pos, commit);
+    }
+    ordered
+}
+
+// applies `hashes` on top of HEAD, oldest first
+pub fn cherry_pick(hashes: &[String]) -> std::io::Result<()> {
+    let status = Command::new("git")
+        .arg("cherry-pick")
+        .args(hashes)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("git cherry-pick failed"))
+    }
+}
+
+// reverts `hash` into the index and working tree without committing, so the
+// generated message can be edited first; `mainline` is the parent number kept
+// when reverting a merge
+pub fn revert_commit(hash: &str, mainline: Option<usize>) -> std::io::Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.args(["revert", "--no-commit"]);
+    if let Some(parent) = mainline {
+        cmd.args(["-m", &parent.to_string()]);
+    }
+    let status = cmd
+        .arg(hash)
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("git revert failed"))
+    }
+}
+
+// message git prepared for the pending merge, revert or cherry-pick
+pub fn prepared_message() -> String {
+    let Ok(dir) = crate::git_rebase::git_dir() else {
+        return String::new();
+    };
+    std::fs::read_to_string(dir.join("MERGE_MSG"))
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
 pub fn reset_to(hash: &str, strength: &str) -> std::io::Result<()> {
     let strength = strength.trim().to_lowercase();
     if !["soft", "mixed", "hard"].contains(&strength.as_str()) {
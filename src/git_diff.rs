@@ -1,23 +1,1191 @@
+use crate::state::{
+    Diff, DiffAlgorithm, DiffFile, DiffHunk, DiffLine, DiffLineKind, DiffOptions, DiffRow,
+    DiffSearch, DiffSource, DiffTarget,
+};
 use ratatui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{self, List, ListItem},
 };
-use std::process::Command;
+use regex::{Regex, RegexBuilder};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    ops::Range,
+    process::{Command, Stdio},
+};
+use unicode_width::UnicodeWidthChar;
+
+pub fn load_diff(target: &DiffTarget, options: &DiffOptions) -> Vec<DiffFile> {
+    let mut files = Vec::new();
+    match target {
+        DiffTarget::WorkingTree => {
+            files.extend(run_diff(&["diff", "--cached"], DiffSource::Staged, options));
+            files.extend(run_diff(&["diff"], DiffSource::Unstaged, options));
+            for path in untracked_files() {
+                files.extend(untracked_diff(&path, options));
+            }
+        }
+        DiffTarget::File {
+            path,
+            staged,
+            unstaged,
+            untracked,
+        } => {
+            if *staged {
+                files.extend(run_diff(
+                    &["diff", "--cached", "--", path],
+                    DiffSource::Staged,
+                    options,
+                ));
+            }
+            if *unstaged {
+                files.extend(run_diff(
+                    &["diff", "--", path],
+                    DiffSource::Unstaged,
+                    options,
+                ));
+            }
+            if *untracked {
+                files.extend(untracked_diff(path, options));
+            }
+        }
+        DiffTarget::Commit(hash) => {
+            files.extend(run_diff(
+                &["show", "--format=", "--diff-merges=first-parent", hash],
+                DiffSource::Revision,
+                options,
+            ));
+        }
+        DiffTarget::CommitFile {
+            hash,
+            path,
+            old_path,
+        } => {
+            let mut args = vec![
+                "show",
+                "--format=",
+                "--diff-merges=first-parent",
+                hash,
+                "--",
+            ];
+            args.extend(old_path.as_deref());
+            args.push(path);
+            files.extend(run_diff(&args, DiffSource::Revision, options));
+        }
+        DiffTarget::Branch(name) => {
+            files.extend(run_diff(
+                &["diff", "HEAD", name],
+                DiffSource::Revision,
+                options,
+            ));
+        }
+    }
+    mark_word_changes(&mut files);
+    files
+}
 
-pub fn load_diff() -> String {
+fn untracked_files() -> Vec<String> {
     let output = Command::new("git")
-        .args(["diff"])
+        .args(["ls-files", "--others", "--exclude-standard", "-z"])
         .output()
-        .expect("failed to execute git status command.")
+        .expect("failed to execute git ls-files command.")
         .stdout;
 
-    String::from_utf8(output).unwrap_or_default()
+    String::from_utf8_lossy(&output)
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string())
+        .collect()
+}
+
+// `git diff --no-index` against /dev/null renders the whole file as added
+fn untracked_diff(path: &str, options: &DiffOptions) -> Vec<DiffFile> {
+    run_diff(
+        &["diff", "--no-index", "--", "/dev/null", path],
+        DiffSource::Untracked,
+        options,
+    )
+}
+
+fn run_diff(args: &[&str], source: DiffSource, options: &DiffOptions) -> Vec<DiffFile> {
+    let (cmd, rest) = args.split_first().expect("empty git diff command");
+    let output = Command::new("git")
+        .arg(cmd)
+        .args(["--no-color", "--no-ext-diff"])
+        .args(option_args(options))
+        .args(rest)
+        .output()
+        .expect("failed to execute git diff command.")
+        .stdout;
+
+    parse_diff(&String::from_utf8_lossy(&output), source)
+}
+
+fn option_args(options: &DiffOptions) -> Vec<String> {
+    let mut args = vec![format!("--unified={}", options.context_lines)];
+    if options.ignore_whitespace {
+        args.push("--ignore-all-space".to_string());
+    }
+    match options.algorithm {
+        DiffAlgorithm::Myers => {}
+        algorithm => args.push(format!("--diff-algorithm={}", algorithm_name(algorithm))),
+    }
+    if options.find_copies {
+        args.push("--find-copies".to_string());
+    } else if options.find_renames {
+        args.push("--find-renames".to_string());
+    } else {
+        args.push("--no-renames".to_string());
+    }
+    args
+}
+
+pub fn algorithm_name(algorithm: DiffAlgorithm) -> &'static str {
+    match algorithm {
+        DiffAlgorithm::Myers => "myers",
+        DiffAlgorithm::Minimal => "minimal",
+        DiffAlgorithm::Patience => "patience",
+        DiffAlgorithm::Histogram => "histogram",
+    }
+}
+
+pub fn next_algorithm(algorithm: DiffAlgorithm) -> DiffAlgorithm {
+    match algorithm {
+        DiffAlgorithm::Myers => DiffAlgorithm::Minimal,
+        DiffAlgorithm::Minimal => DiffAlgorithm::Patience,
+        DiffAlgorithm::Patience => DiffAlgorithm::Histogram,
+        DiffAlgorithm::Histogram => DiffAlgorithm::Myers,
+    }
+}
+
+pub fn prev_algorithm(algorithm: DiffAlgorithm) -> DiffAlgorithm {
+    match algorithm {
+        DiffAlgorithm::Myers => DiffAlgorithm::Histogram,
+        DiffAlgorithm::Minimal => DiffAlgorithm::Myers,
+        DiffAlgorithm::Patience => DiffAlgorithm::Minimal,
+        DiffAlgorithm::Histogram => DiffAlgorithm::Patience,
+    }
+}
+
+pub fn build_rows(files: &[DiffFile], split: bool) -> Vec<DiffRow> {
+    let mut rows = Vec::new();
+    let mut section = None;
+    for (fi, file) in files.iter().enumerate() {
+        if file.source != DiffSource::Revision && section != Some(file.source) {
+            rows.push(DiffRow::Section(file.source));
+            section = Some(file.source);
+        }
+        if file.folded {
+            rows.push(DiffRow::FileHeader { file: fi, line: 0 });
+            continue;
+        }
+        for line in 0..file.header.len() {
+            rows.push(DiffRow::FileHeader { file: fi, line });
+        }
+        for (hi, hunk) in file.hunks.iter().enumerate() {
+            rows.push(DiffRow::HunkHeader { file: fi, hunk: hi });
+            if split {
+                for (old, new) in pair_lines(hunk) {
+                    rows.push(DiffRow::Pair {
+                        file: fi,
+                        hunk: hi,
+                        old,
+                        new,
+                    });
+                }
+            } else {
+                for line in 0..hunk.lines.len() {
+                    rows.push(DiffRow::Line {
+                        file: fi,
+                        hunk: hi,
+                        line,
+                    });
+                }
+            }
+        }
+    }
+    rows
+}
+
+// lines of a hunk as (old, new) columns: a run of removals is paired with the
+// run of additions that follows it, the shorter side padded with `None`
+fn pair_lines(hunk: &DiffHunk) -> Vec<(Option<usize>, Option<usize>)> {
+    fn flush(
+        out: &mut Vec<(Option<usize>, Option<usize>)>,
+        removed: &mut Vec<usize>,
+        added: &mut Vec<usize>,
+    ) {
+        for k in 0..removed.len().max(added.len()) {
+            out.push((removed.get(k).copied(), added.get(k).copied()));
+        }
+        removed.clear();
+        added.clear();
+    }
+
+    let mut out = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut last = DiffLineKind::Context;
+
+    for (i, line) in hunk.lines.iter().enumerate() {
+        match (line.kind, last) {
+            (DiffLineKind::Removed, _) | (DiffLineKind::NoNewline, DiffLineKind::Removed) => {
+                if !added.is_empty() {
+                    flush(&mut out, &mut removed, &mut added);
+                }
+                removed.push(i);
+            }
+            (DiffLineKind::Added, _) | (DiffLineKind::NoNewline, DiffLineKind::Added) => {
+                added.push(i);
+            }
+            _ => {
+                flush(&mut out, &mut removed, &mut added);
+                out.push((Some(i), Some(i)));
+            }
+        }
+        if line.kind != DiffLineKind::NoNewline {
+            last = line.kind;
+        }
+    }
+    flush(&mut out, &mut removed, &mut added);
+    out
+}
+
+type ByteRanges = Vec<Range<usize>>;
+
+// longer lines are left without word emphasis, the token LCS is quadratic
+const MAX_WORD_DIFF_TOKENS: usize = 300;
+
+// fills `DiffLine::emphasis` for removed/added lines that replace each other
+fn mark_word_changes(files: &mut [DiffFile]) {
+    for file in files {
+        for hunk in &mut file.hunks {
+            for (old, new) in pair_lines(hunk) {
+                let (Some(old), Some(new)) = (old, new) else {
+                    continue;
+                };
+                if hunk.lines[old].kind != DiffLineKind::Removed
+                    || hunk.lines[new].kind != DiffLineKind::Added
+                {
+                    continue;
+                }
+                if let Some((a, b)) =
+                    word_changes(&hunk.lines[old].content, &hunk.lines[new].content)
+                {
+                    hunk.lines[old].emphasis = a;
+                    hunk.lines[new].emphasis = b;
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let mut prev_class = None;
+    for (i, c) in s.char_indices() {
+        let class = if c.is_alphanumeric() || c == '_' {
+            Some(0)
+        } else if c.is_whitespace() {
+            Some(1)
+        } else {
+            // punctuation is a token on its own
+            None
+        };
+        match tokens.last_mut() {
+            Some(last) if class.is_some() && class == prev_class => last.end = i + c.len_utf8(),
+            _ => tokens.push(i..i + c.len_utf8()),
+        }
+        prev_class = class;
+    }
+    tokens
+}
+
+// changed byte ranges of `old` and `new`, or `None` when the lines have too
+// little in common for word emphasis to be useful
+fn word_changes(old: &str, new: &str) -> Option<(ByteRanges, ByteRanges)> {
+    let a = tokenize(old);
+    let b = tokenize(new);
+    if a.len() > MAX_WORD_DIFF_TOKENS || b.len() > MAX_WORD_DIFF_TOKENS {
+        return None;
+    }
+
+    // lcs[i][j]: common tokens of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if old[a[i].clone()] == new[b[j].clone()] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut same_a = vec![false; a.len()];
+    let mut same_b = vec![false; b.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if old[a[i].clone()] == new[b[j].clone()] {
+            same_a[i] = true;
+            same_b[j] = true;
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let kept = |tokens: &[Range<usize>], same: &[bool]| -> usize {
+        tokens
+            .iter()
+            .zip(same)
+            .filter(|(_, s)| **s)
+            .map(|(t, _)| t.len())
+            .sum()
+    };
+    if kept(&a, &same_a) * 2 < old.len() || kept(&b, &same_b) * 2 < new.len() {
+        return None;
+    }
+
+    Some((changed_ranges(&a, &same_a), changed_ranges(&b, &same_b)))
+}
+
+fn changed_ranges(tokens: &[Range<usize>], same: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (t, s) in tokens.iter().zip(same) {
+        if *s {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.end == t.start => last.end = t.end,
+            _ => ranges.push(t.clone()),
+        }
+    }
+    ranges
+}
+
+// index of the row in `rows` showing the same content as `row` from the other layout
+pub fn find_row(rows: &[DiffRow], row: DiffRow) -> Option<usize> {
+    rows.iter().position(|r| match (*r, row) {
+        (
+            DiffRow::Pair {
+                file,
+                hunk,
+                old,
+                new,
+                ..
+            },
+            DiffRow::Line {
+                file: f,
+                hunk: h,
+                line,
+            },
+        ) => file == f && hunk == h && (old == Some(line) || new == Some(line)),
+        (
+            DiffRow::Line { file, hunk, line },
+            DiffRow::Pair {
+                file: f,
+                hunk: h,
+                old,
+                new,
+            },
+        ) => file == f && hunk == h && old.or(new) == Some(line),
+        (a, b) => a == b,
+    })
+}
+
+pub fn parse_diff(text: &str, source: DiffSource) -> Vec<DiffFile> {
+    let mut files: Vec<DiffFile> = Vec::new();
+    let (mut old_no, mut new_no) = (0, 0);
+
+    for line in text.lines() {
+        if line.starts_with("diff --git ") {
+            files.push(DiffFile {
+                path: path_from_diff_line(line),
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+                source,
+                folded: false,
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if line.starts_with("@@")
+            && let Some(hunk) = parse_hunk_header(line)
+        {
+            old_no = hunk.old_start;
+            new_no = hunk.new_start;
+            file.hunks.push(hunk);
+            continue;
+        }
+
+        let Some(hunk) = file.hunks.last_mut() else {
+            if let Some(path) = line.strip_prefix("+++ b/") {
+                file.path = path.trim_end_matches('\t').to_string();
+            }
+            file.header.push(line.to_string());
+            continue;
+        };
+
+        let (kind, content) = match line.chars().next() {
+            Some('+') => (DiffLineKind::Added, &line[1..]),
+            Some('-') => (DiffLineKind::Removed, &line[1..]),
+            Some('\\') => (DiffLineKind::NoNewline, &line[1..]),
+            Some(' ') => (DiffLineKind::Context, &line[1..]),
+            _ => (DiffLineKind::Context, line),
+        };
+        let old_lineno = matches!(kind, DiffLineKind::Context | DiffLineKind::Removed).then(|| {
+            old_no += 1;
+            old_no - 1
+        });
+        let new_lineno = matches!(kind, DiffLineKind::Context | DiffLineKind::Added).then(|| {
+            new_no += 1;
+            new_no - 1
+        });
+        hunk.lines.push(DiffLine {
+            kind,
+            content: content.to_string(),
+            old_lineno,
+            new_lineno,
+            highlights: Vec::new(),
+            emphasis: Vec::new(),
+        });
+    }
+
+    files
+}
+
+fn path_from_diff_line(line: &str) -> String {
+    // diff --git a/src/main.rs b/src/main.rs
+    let rest = line.trim_start_matches("diff --git ");
+    match rest.rsplit_once(" b/") {
+        Some((_, new)) => new.to_string(),
+        None => rest.to_string(),
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    // @@ -12,7 +12,8 @@ fn main() {
+    let rest = line.strip_prefix("@@ ")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(' ')?;
+    let (old_start, old_count) = parse_range(old.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(new.strip_prefix('+')?)?;
+
+    Some(DiffHunk {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+        section: section.trim_start().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+fn parse_range(s: &str) -> Option<(u32, u32)> {
+    match s.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((s.parse().ok()?, 1)),
+    }
+}
+
+pub fn hunk_header_text(hunk: &DiffHunk) -> String {
+    let mut s = format!(
+        "@@ -{},{} +{},{} @@",
+        hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count
+    );
+    if !hunk.section.is_empty() {
+        s.push(' ');
+        s.push_str(&hunk.section);
+    }
+    s
+}
+
+pub fn line_text(line: &DiffLine) -> String {
+    let prefix = match line.kind {
+        DiffLineKind::Context => ' ',
+        DiffLineKind::Added => '+',
+        DiffLineKind::Removed => '-',
+        DiffLineKind::NoNewline => '\\',
+    };
+    format!("{}{}", prefix, line.content)
 }
 
-pub fn widget(diff: &str, focused: bool) -> List<'_> {
-    let block = widgets::Block::bordered().title("Diff");
+pub fn section_title(source: DiffSource) -> &'static str {
+    match source {
+        DiffSource::Staged => "Staged changes",
+        DiffSource::Unstaged => "Unstaged changes",
+        DiffSource::Untracked => "Untracked files",
+        DiffSource::Revision => "",
+    }
+}
+
+pub fn is_hunk_start(row: &DiffRow) -> bool {
+    matches!(row, DiffRow::HunkHeader { .. })
+}
+
+pub fn is_file_start(row: &DiffRow) -> bool {
+    matches!(row, DiffRow::FileHeader { line: 0, .. })
+}
+
+// nearest row after (or before) `from` matching `pred`
+pub fn find_next(
+    diff: &Diff,
+    from: usize,
+    forward: bool,
+    pred: fn(&DiffRow) -> bool,
+) -> Option<usize> {
+    if forward {
+        (from + 1..diff.rows.len()).find(|&i| pred(&diff.rows[i]))
+    } else {
+        (0..from.min(diff.rows.len()))
+            .rev()
+            .find(|&i| pred(&diff.rows[i]))
+    }
+}
+
+// collapses or expands the file under `row`, returns the row of its header
+pub fn toggle_fold(
+    diff: &mut Diff,
+    folded: &mut HashSet<(DiffSource, String)>,
+    row: usize,
+    split: bool,
+) -> Option<usize> {
+    let fi = file_at(diff, row)?;
+    let file = &mut diff.files[fi];
+    file.folded = !file.folded;
+    let key = (file.source, file.path.clone());
+    if file.folded {
+        folded.insert(key);
+    } else {
+        folded.remove(&key);
+    }
+    diff.rows = build_rows(&diff.files, split);
+    find_row(&diff.rows, DiffRow::FileHeader { file: fi, line: 0 })
+}
+
+// folds every file, or unfolds them all if everything is folded already
+pub fn toggle_fold_all(diff: &mut Diff, folded: &mut HashSet<(DiffSource, String)>, split: bool) {
+    let fold = diff.files.iter().any(|f| !f.folded);
+    for file in &mut diff.files {
+        file.folded = fold;
+        let key = (file.source, file.path.clone());
+        if fold {
+            folded.insert(key);
+        } else {
+            folded.remove(&key);
+        }
+    }
+    diff.rows = build_rows(&diff.files, split);
+}
+
+pub fn search_matcher(query: &str, regex: bool, ignore_case: bool) -> Option<Regex> {
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .ok()
+}
+
+// rows containing a match, in display order
+fn search_hits(diff: &Diff, matcher: &Regex) -> Vec<usize> {
+    diff.rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| match **row {
+            DiffRow::Section(source) => matcher.is_match(section_title(source)),
+            DiffRow::FileHeader { file, line } => matcher.is_match(&diff.files[file].header[line]),
+            DiffRow::HunkHeader { file, hunk } => {
+                matcher.is_match(&hunk_header_text(&diff.files[file].hunks[hunk]))
+            }
+            DiffRow::Line { file, hunk, line } => {
+                matcher.is_match(&diff.files[file].hunks[hunk].lines[line].content)
+            }
+            DiffRow::Pair {
+                file,
+                hunk,
+                old,
+                new,
+            } => {
+                let lines = &diff.files[file].hunks[hunk].lines;
+                old.into_iter()
+                    .chain(new)
+                    .any(|l| matcher.is_match(&lines[l].content))
+            }
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+pub fn update_search_hits(diff: &Diff, search: &mut DiffSearch) {
+    search.hits = search
+        .matcher
+        .as_ref()
+        .map(|m| search_hits(diff, m))
+        .unwrap_or_default();
+}
+
+// first hit after (or before) `from`, wrapping around
+pub fn next_hit(hits: &[usize], from: usize, forward: bool) -> Option<usize> {
+    if forward {
+        hits.iter().find(|&&h| h > from).or(hits.first()).copied()
+    } else {
+        hits.iter()
+            .rev()
+            .find(|&&h| h < from)
+            .or(hits.last())
+            .copied()
+    }
+}
+
+// (file, hunk) under the given row, if the row belongs to a hunk
+pub fn hunk_at(diff: &Diff, row: usize) -> Option<(usize, usize)> {
+    match diff.rows.get(row)? {
+        DiffRow::HunkHeader { file, hunk }
+        | DiffRow::Line { file, hunk, .. }
+        | DiffRow::Pair { file, hunk, .. } => Some((*file, *hunk)),
+        DiffRow::Section(_) | DiffRow::FileHeader { .. } => None,
+    }
+}
+
+pub fn file_at(diff: &Diff, row: usize) -> Option<usize> {
+    match diff.rows.get(row)? {
+        DiffRow::FileHeader { file, .. }
+        | DiffRow::HunkHeader { file, .. }
+        | DiffRow::Line { file, .. }
+        | DiffRow::Pair { file, .. } => Some(*file),
+        DiffRow::Section(_) => None,
+    }
+}
+
+// stages (or unstages) the +/- lines between `anchor` and `cursor`, limited to the file under the cursor
+pub fn toggle_range(diff: &Diff, anchor: usize, cursor: usize) -> io::Result<()> {
+    let Some(target) = file_at(diff, cursor) else {
+        return Ok(());
+    };
+    let (from, to) = (anchor.min(cursor), anchor.max(cursor));
+    let selected: Vec<(usize, usize)> = diff.rows[from..=to.min(diff.rows.len() - 1)]
+        .iter()
+        .flat_map(|row| match *row {
+            DiffRow::Line { file, hunk, line } if file == target => vec![(hunk, line)],
+            DiffRow::Pair {
+                file,
+                hunk,
+                old,
+                new,
+            } if file == target => old.into_iter().chain(new).map(|l| (hunk, l)).collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+    toggle_lines(&diff.files[target], &selected)
+}
+
+pub fn hunk_patch(file: &DiffFile, hunk: &DiffHunk) -> String {
+    build_patch(&file.header, std::slice::from_ref(hunk))
+}
+
+fn build_patch(header: &[String], hunks: &[DiffHunk]) -> String {
+    let mut patch = String::new();
+    for line in header {
+        patch.push_str(line);
+        patch.push('\n');
+    }
+    for hunk in hunks {
+        patch.push_str(&hunk_header_text(hunk));
+        patch.push('\n');
+        for line in &hunk.lines {
+            patch.push_str(&line_text(line));
+            patch.push('\n');
+        }
+    }
+    patch
+}
+
+// stages an unstaged hunk, or unstages a staged one
+pub fn toggle_hunk(file: &DiffFile, hunk: &DiffHunk) -> io::Result<()> {
+    if file.source == DiffSource::Revision {
+        return Ok(());
+    }
+    apply_to_index(
+        &hunk_patch(file, hunk),
+        file.source == DiffSource::Staged,
+        !has_context(hunk),
+    )
+}
+
+// stages (or unstages) only the selected `(hunk, line)` pairs of `file`
+fn toggle_lines(file: &DiffFile, selected: &[(usize, usize)]) -> io::Result<()> {
+    if file.source == DiffSource::Revision {
+        return Ok(());
+    }
+    let reverse = file.source == DiffSource::Staged;
+    let mut hunks = Vec::new();
+    let mut delta: i64 = 0;
+    let mut partial = false;
+
+    for (hi, hunk) in file.hunks.iter().enumerate() {
+        let picked: Vec<bool> = (0..hunk.lines.len())
+            .map(|li| selected.contains(&(hi, li)))
+            .collect();
+        let Some(mut h) = partial_hunk(hunk, &picked, reverse) else {
+            partial = true;
+            continue;
+        };
+        if h.old_count != hunk.old_count || h.new_count != hunk.new_count {
+            partial = true;
+        }
+
+        // the side git matches against keeps its position, the other one follows
+        if reverse {
+            h.old_start = shifted_start(h.new_start, h.new_count, h.old_count, -delta);
+        } else {
+            h.new_start = shifted_start(h.old_start, h.old_count, h.new_count, delta);
+        }
+        delta += i64::from(h.new_count) - i64::from(h.old_count);
+        hunks.push(h);
+    }
+
+    if hunks.is_empty() {
+        return Ok(());
+    }
+
+    let header = if partial {
+        partial_header(file, reverse)
+    } else {
+        file.header.clone()
+    };
+    let zero_context = !file.hunks.iter().any(has_context);
+    apply_to_index(&build_patch(&header, &hunks), reverse, zero_context)
+}
+
+fn shifted_start(start: u32, count: u32, other_count: u32, delta: i64) -> u32 {
+    // a zero-length range points at the line before it
+    let mut s = i64::from(start) + delta;
+    if count == 0 && other_count > 0 {
+        s += 1;
+    } else if count > 0 && other_count == 0 {
+        s -= 1;
+    }
+    s.max(0) as u32
+}
+
+// a partially applied deletion (or reverse-applied creation) is a plain modification
+fn partial_header(file: &DiffFile, reverse: bool) -> Vec<String> {
+    let (mode_prefix, null_line, replacement) = if reverse {
+        (
+            "new file mode",
+            "--- /dev/null",
+            format!("--- a/{}", file.path),
+        )
+    } else {
+        (
+            "deleted file mode",
+            "+++ /dev/null",
+            format!("+++ b/{}", file.path),
+        )
+    };
+    if !file.header.iter().any(|l| l.starts_with(mode_prefix)) {
+        return file.header.clone();
+    }
+    file.header
+        .iter()
+        .filter(|l| !l.starts_with(mode_prefix) && !l.starts_with("index "))
+        .map(|l| {
+            if l == null_line {
+                replacement.clone()
+            } else {
+                l.clone()
+            }
+        })
+        .collect()
+}
+
+// Rewrites `hunk` so that only the picked +/- lines are applied. When staging,
+// unpicked removals become context and unpicked additions are dropped; when
+// unstaging (reverse apply) it is the other way around.
+fn partial_hunk(hunk: &DiffHunk, picked: &[bool], reverse: bool) -> Option<DiffHunk> {
+    let (keep_kind, other_kind) = if reverse {
+        (DiffLineKind::Added, DiffLineKind::Removed)
+    } else {
+        (DiffLineKind::Removed, DiffLineKind::Added)
+    };
+
+    let mut lines: Vec<DiffLine> = Vec::new();
+    let mut any_picked = false;
+    let mut skip_marker = false;
+
+    for (i, line) in hunk.lines.iter().enumerate() {
+        match line.kind {
+            DiffLineKind::Context => {
+                lines.push(line.clone());
+                skip_marker = false;
+            }
+            DiffLineKind::NoNewline => {
+                if !skip_marker {
+                    lines.push(line.clone());
+                }
+            }
+            _ if picked[i] => {
+                any_picked = true;
+                lines.push(line.clone());
+                skip_marker = false;
+            }
+            kind if kind == other_kind => {
+                skip_marker = true;
+            }
+            _ => {
+                // unpicked line that exists on the side being matched: keep as context
+                let no_newline = hunk
+                    .lines
+                    .get(i + 1)
+                    .is_some_and(|l| l.kind == DiffLineKind::NoNewline);
+                let more_after = hunk.lines[i + 1..]
+                    .iter()
+                    .enumerate()
+                    .any(|(j, l)| l.kind == other_kind && picked[i + 1 + j]);
+                if no_newline && more_after {
+                    // the last line gains a newline on the other side
+                    lines.push(DiffLine {
+                        kind: keep_kind,
+                        ..line.clone()
+                    });
+                    lines.push(hunk.lines[i + 1].clone());
+                    lines.push(DiffLine {
+                        kind: other_kind,
+                        ..line.clone()
+                    });
+                    // marker already emitted above
+                    skip_marker = true;
+                } else {
+                    lines.push(DiffLine {
+                        kind: DiffLineKind::Context,
+                        ..line.clone()
+                    });
+                    skip_marker = false;
+                }
+            }
+        }
+    }
+
+    if !any_picked {
+        return None;
+    }
+
+    let count = |k: DiffLineKind| lines.iter().filter(|l| l.kind == k).count() as u32;
+    let context = count(DiffLineKind::Context);
+    Some(DiffHunk {
+        old_start: hunk.old_start,
+        old_count: context + count(DiffLineKind::Removed),
+        new_start: hunk.new_start,
+        new_count: context + count(DiffLineKind::Added),
+        section: hunk.section.clone(),
+        lines,
+    })
+}
+
+fn has_context(hunk: &DiffHunk) -> bool {
+    hunk.lines.iter().any(|l| l.kind == DiffLineKind::Context)
+}
+
+// `zero_context` is needed for patches generated with `--unified=0`
+pub fn apply_to_index(patch: &str, reverse: bool, zero_context: bool) -> io::Result<()> {
+    let mut args = vec!["apply", "--cached", "--whitespace=nowarn"];
+    if reverse {
+        args.push("--reverse");
+    }
+    if zero_context {
+        args.push("--unidiff-zero");
+    }
+    args.push("-");
+
+    let mut child = Command::new("git")
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("git apply --cached failed"))
+    }
+}
+
+const GUTTER_WIDTH: usize = 10;
+
+fn lineno(n: Option<u32>) -> String {
+    n.map(|n| n.to_string()).unwrap_or_default()
+}
+
+fn match_style(style: Style) -> Style {
+    style.bg(Color::LightMagenta).fg(Color::Black)
+}
+
+// `text` with search matches highlighted
+fn text_spans<'a>(text: &'a str, style: Style, search: Option<&Regex>) -> Vec<Span<'a>> {
+    let Some(re) = search else {
+        return vec![Span::styled(text, style)];
+    };
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for m in re.find_iter(text).filter(|m| !m.is_empty()) {
+        if m.start() > last {
+            spans.push(Span::styled(&text[last..m.start()], style));
+        }
+        spans.push(Span::styled(m.as_str(), match_style(style)));
+        last = m.end();
+    }
+    if last < text.len() || spans.is_empty() {
+        spans.push(Span::styled(&text[last..], style));
+    }
+    spans
+}
+
+fn content_spans<'a>(line: &'a DiffLine, base: Style, search: Option<&Regex>) -> Vec<Span<'a>> {
+    if line.highlights.is_empty() && line.emphasis.is_empty() {
+        return text_spans(&line.content, base, search);
+    }
+    let emphasized = match line.kind {
+        DiffLineKind::Added => base
+            .bg(Color::Rgb(0x2f, 0x6f, 0x2f))
+            .add_modifier(Modifier::BOLD),
+        DiffLineKind::Removed => base
+            .bg(Color::Rgb(0x8f, 0x2f, 0x2f))
+            .add_modifier(Modifier::BOLD),
+        _ => base,
+    };
+    let matches: Vec<Range<usize>> = search
+        .map(|re| {
+            re.find_iter(&line.content)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // split the line at every syntax, emphasis and search match boundary
+    let mut cuts: Vec<usize> = vec![0, line.content.len()];
+    for r in line
+        .highlights
+        .iter()
+        .map(|(_, r)| r)
+        .chain(line.emphasis.iter())
+        .chain(matches.iter())
+    {
+        cuts.push(r.start);
+        cuts.push(r.end);
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    cuts.windows(2)
+        .filter_map(|w| {
+            let text = line.content.get(w[0]..w[1])?;
+            let mut style = if line.emphasis.iter().any(|r| r.contains(&w[0])) {
+                emphasized
+            } else {
+                base
+            };
+            if let Some((color, _)) = line.highlights.iter().find(|(_, r)| r.contains(&w[0])) {
+                style = style.fg(*color);
+            }
+            if matches.iter().any(|r| r.contains(&w[0])) {
+                style = match_style(style);
+            }
+            Some(Span::styled(text, style))
+        })
+        .collect()
+}
+
+fn line_spans<'a>(l: &'a DiffLine, gutter: String, search: Option<&Regex>) -> Vec<Span<'a>> {
+    let highlighted = !l.highlights.is_empty();
+    let (prefix, base) = match l.kind {
+        DiffLineKind::Context => (" ", Style::new()),
+        DiffLineKind::Added if highlighted => ("+", Style::new().bg(Color::Rgb(0x1f, 0x3a, 0x1f))),
+        DiffLineKind::Added => ("+", Style::new().fg(Color::Green)),
+        DiffLineKind::Removed if highlighted => {
+            ("-", Style::new().bg(Color::Rgb(0x4a, 0x1f, 0x1f)))
+        }
+        DiffLineKind::Removed => ("-", Style::new().fg(Color::Red)),
+        DiffLineKind::NoNewline => (
+            "\\",
+            Style::new()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+        ),
+    };
+    let prefix_style = match l.kind {
+        DiffLineKind::Added => base.fg(Color::Green),
+        DiffLineKind::Removed => base.fg(Color::Red),
+        _ => base,
+    };
+
+    let mut spans = vec![
+        Span::styled(gutter, Style::new().fg(Color::DarkGray)),
+        Span::styled(prefix, prefix_style),
+    ];
+    spans.extend(content_spans(l, base, search));
+    spans
+}
+
+// cuts `spans` to exactly `width` columns, padding with spaces
+fn fit_spans(spans: Vec<Span<'_>>, width: usize) -> Vec<Span<'_>> {
+    let mut out = Vec::new();
+    let mut used = 0;
+    for span in spans {
+        let mut text = String::new();
+        for c in span.content.replace('\t', "    ").chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            text.push(c);
+        }
+        out.push(Span::styled(text, span.style));
+        if used >= width {
+            break;
+        }
+    }
+    out.push(Span::raw(" ".repeat(width - used)));
+    out
+}
+
+fn split_side<'a>(
+    lines: &'a [DiffLine],
+    idx: Option<usize>,
+    old: bool,
+    width: usize,
+    search: Option<&Regex>,
+) -> Vec<Span<'a>> {
+    let Some(l) = idx.map(|i| &lines[i]) else {
+        return fit_spans(Vec::new(), width);
+    };
+    let n = if old { l.old_lineno } else { l.new_lineno };
+    fit_spans(line_spans(l, format!("{:>4} ", lineno(n)), search), width)
+}
+
+// `width` is the inner width of the panel, used to lay out side-by-side rows
+pub fn row_line<'a>(diff: &'a Diff, row: DiffRow, width: u16, search: Option<&Regex>) -> Line<'a> {
+    match row {
+        DiffRow::Section(source) => Line::from(text_spans(
+            section_title(source),
+            Style::new()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            search,
+        )),
+        DiffRow::FileHeader { file, line: 0 } => {
+            let f = &diff.files[file];
+            let marker = if f.folded { "▸ " } else { "▾ " };
+            let mut spans = vec![Span::styled(marker, Style::new().fg(Color::DarkGray))];
+            spans.extend(text_spans(
+                &f.header[0],
+                Style::new().add_modifier(Modifier::BOLD),
+                search,
+            ));
+            Line::from(spans)
+        }
+        DiffRow::FileHeader { file, line } => Line::from(text_spans(
+            &diff.files[file].header[line],
+            Style::new().add_modifier(Modifier::BOLD),
+            search,
+        )),
+        DiffRow::HunkHeader { file, hunk } => {
+            let header = hunk_header_text(&diff.files[file].hunks[hunk]);
+            let mut spans = vec![Span::raw(" ".repeat(GUTTER_WIDTH))];
+            spans.extend(
+                text_spans(&header, Style::new().fg(Color::Cyan), search)
+                    .into_iter()
+                    .map(|span| Span::styled(span.content.into_owned(), span.style)),
+            );
+            Line::from(spans)
+        }
+        DiffRow::Line { file, hunk, line } => {
+            let l = &diff.files[file].hunks[hunk].lines[line];
+            let gutter = format!("{:>4} {:>4} ", lineno(l.old_lineno), lineno(l.new_lineno));
+            Line::from(line_spans(l, gutter, search))
+        }
+        DiffRow::Pair {
+            file,
+            hunk,
+            old,
+            new,
+        } => {
+            let lines = &diff.files[file].hunks[hunk].lines;
+            let half = (width as usize).saturating_sub(1) / 2;
+            let mut spans = split_side(lines, old, true, half, search);
+            spans.push(Span::styled("│", Style::new().fg(Color::DarkGray)));
+            spans.extend(split_side(lines, new, false, half, search));
+            Line::from(spans)
+        }
+    }
+}
+
+pub fn widget<'a>(
+    diff: &'a Diff,
+    target: &DiffTarget,
+    focused: bool,
+    visual: Option<(usize, usize)>,
+    width: u16,
+    search: Option<&DiffSearch>,
+) -> List<'a> {
+    let mut title = match target {
+        DiffTarget::WorkingTree => "Diff".to_string(),
+        DiffTarget::File { path, .. } => format!("Diff - {}", path),
+        DiffTarget::Commit(hash) => format!("Diff - commit {}", hash),
+        DiffTarget::CommitFile { hash, path, .. } => {
+            format!("Diff - commit {} - {}", &hash[..hash.len().min(7)], path)
+        }
+        DiffTarget::Branch(name) => format!("Diff - HEAD..{}", name),
+    };
+    if visual.is_some() {
+        title.push_str(" [VISUAL]");
+    }
+    if let Some(search) = search {
+        match search.matcher {
+            Some(_) => title.push_str(&format!(
+                " [/{}: {} matches]",
+                search.query,
+                search.hits.len()
+            )),
+            None => title.push_str(&format!(" [/{}: invalid pattern]", search.query)),
+        }
+    }
+    let block = widgets::Block::bordered().title(title);
+    let matcher = search.and_then(|s| s.matcher.as_ref());
 
-    let items: Vec<ListItem> = diff.lines().map(|e| ListItem::new(e.to_string())).collect();
+    let items: Vec<ListItem> = diff
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut line = row_line(diff, *row, width.saturating_sub(2), matcher);
+            if let Some((from, to)) = visual
+                && (from..=to).contains(&i)
+            {
+                for span in &mut line.spans {
+                    span.style = span.style.bg(Color::DarkGray);
+                }
+            }
+            ListItem::new(line)
+        })
+        .collect();
 
     let list = widgets::List::new(items).block(block);
     if focused {
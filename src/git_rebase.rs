@@ -0,0 +1,255 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{self, List, ListItem},
+};
+
+use crate::state::{CommitInfo, GitOperation, RebaseAction, RebaseEntry, RebasePlan};
+
+// working files of the rebase editor live in `<git dir>/rust_gitui`
+const WORK_DIR: &str = "rust_gitui";
+
+pub fn action_name(action: RebaseAction) -> &'static str {
+    match action {
+        RebaseAction::Pick => "pick",
+        RebaseAction::Reword => "reword",
+        RebaseAction::Edit => "edit",
+        RebaseAction::Squash => "squash",
+        RebaseAction::Fixup => "fixup",
+        RebaseAction::Drop => "drop",
+    }
+}
+
+fn action_color(action: RebaseAction) -> Color {
+    match action {
+        RebaseAction::Pick => Color::Green,
+        RebaseAction::Reword => Color::Cyan,
+        RebaseAction::Edit => Color::Magenta,
+        RebaseAction::Squash | RebaseAction::Fixup => Color::Yellow,
+        RebaseAction::Drop => Color::Red,
+    }
+}
+
+// plan for rewriting `commits[..=idx]`, newest first as listed in the Commits panel
+pub fn plan_for(commits: &[CommitInfo], idx: usize) -> io::Result<RebasePlan> {
+    let Some(oldest) = commits.get(idx) else {
+        return Err(io::Error::other("no commit selected"));
+    };
+    if commits[..=idx].iter().any(|c| c.parents.len() > 1) {
+        return Err(io::Error::other(
+            "the selected range contains a merge commit",
+        ));
+    }
+    Ok(RebasePlan {
+        base: oldest.parents.first().cloned(),
+        entries: commits[..=idx]
+            .iter()
+            .map(|c| RebaseEntry {
+                action: RebaseAction::Pick,
+                commit: c.clone(),
+                message: None,
+            })
+            .collect(),
+    })
+}
+
+pub fn git_dir() -> io::Result<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--absolute-git-dir"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("not a git repository"));
+    }
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+// todo list in git's order (oldest first); rewords are a pick followed by an
+// amend with the message prepared in the editor, each written to its own file
+fn todo_text(plan: &RebasePlan, work_dir: &std::path::Path) -> io::Result<String> {
+    let mut todo = String::new();
+    for (i, entry) in plan.entries.iter().rev().enumerate() {
+        let hash = &entry.commit.hash;
+        let subject = &entry.commit.subject;
+        match (entry.action, &entry.message) {
+            (RebaseAction::Reword, Some(message)) => {
+                let path = work_dir.join(format!("reword-{}", i));
+                // first line is the subject, like the commit popup
+                let message = match message.split_once('\n') {
+                    Some((subject, body)) => format!("{}\n\n{}", subject.trim(), body.trim()),
+                    None => message.clone(),
+                };
+                fs::write(&path, message)?;
+                todo.push_str(&format!("pick {} {}\n", hash, subject));
+                todo.push_str(&format!(
+                    "exec git commit --amend --allow-empty -q -F {}\n",
+                    shell_quote(&path.to_string_lossy())
+                ));
+            }
+            (action, _) => {
+                todo.push_str(&format!("{} {} {}\n", action_name(action), hash, subject));
+            }
+        }
+    }
+    Ok(todo)
+}
+
+pub fn run_plan(plan: &RebasePlan) -> io::Result<()> {
+    let kept = plan
+        .entries
+        .iter()
+        .rev()
+        .find(|e| e.action != RebaseAction::Drop);
+    if let Some(entry) = kept
+        && matches!(entry.action, RebaseAction::Squash | RebaseAction::Fixup)
+    {
+        return Err(io::Error::other(
+            "the oldest kept commit cannot be a squash or fixup",
+        ));
+    }
+
+    let work_dir = git_dir()?.join(WORK_DIR);
+    fs::create_dir_all(&work_dir)?;
+    let todo_path = work_dir.join("rebase-todo");
+    fs::write(&todo_path, todo_text(plan, &work_dir)?)?;
+
+    let mut cmd = Command::new("git");
+    cmd.args(["rebase", "-i", "--autostash"]);
+    match &plan.base {
+        Some(base) => cmd.arg(base),
+        None => cmd.arg("--root"),
+    };
+    // git hands the todo file to the sequence editor, which replaces it with ours;
+    // squash messages are accepted as git combines them
+    let status = cmd
+        .env(
+            "GIT_SEQUENCE_EDITOR",
+            format!("cp {}", shell_quote(&todo_path.to_string_lossy())),
+        )
+        .env("GIT_EDITOR", "true")
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("git rebase -i failed"))
+    }
+}
+
+// folds `fixup!`/`squash!` commits above `base` into their targets
+pub fn autosquash(base: Option<&str>) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["rebase", "-i", "--autosquash", "--autostash"])
+        .arg(base.unwrap_or("--root"))
+        // accept the todo list git generated
+        .env("GIT_SEQUENCE_EDITOR", "true")
+        .env("GIT_EDITOR", "true")
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("git rebase --autosquash failed"))
+    }
+}
+
+// multi-step operation that stopped half way and waits for the user
+pub fn current_operation() -> Option<GitOperation> {
+    let dir = git_dir().ok()?;
+    if dir.join("rebase-merge").exists() || dir.join("rebase-apply").exists() {
+        return Some(GitOperation::Rebase);
+    }
+    if dir.join("CHERRY_PICK_HEAD").exists() {
+        return Some(GitOperation::CherryPick);
+    }
+    if dir.join("REVERT_HEAD").exists() {
+        return Some(GitOperation::Revert);
+    }
+    None
+}
+
+pub fn operation_name(op: GitOperation) -> &'static str {
+    match op {
+        GitOperation::Rebase => "rebase",
+        GitOperation::CherryPick => "cherry-pick",
+        GitOperation::Revert => "revert",
+    }
+}
+
+// runs `git <op> --continue|--skip|--abort`
+pub fn run_operation(op: GitOperation, step: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args([operation_name(op), step])
+        // keep the prepared message when continuing after a conflict
+        .env("GIT_EDITOR", "true")
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "git {} {} failed",
+            operation_name(op),
+            step
+        )))
+    }
+}
+
+pub fn widget(plan: &RebasePlan, focused: bool) -> List<'_> {
+    let base = plan
+        .base
+        .as_deref()
+        .map_or("root", |b| &b[..b.len().min(7)]);
+    let block = widgets::Block::bordered().title(format!(
+        "Rebase onto {} (p/r/e/s/f/d设置，J/K移动，Enter执行，Esc取消)",
+        base
+    ));
+
+    let items: Vec<ListItem> = plan
+        .entries
+        .iter()
+        .map(|e| {
+            let subject = match &e.message {
+                Some(message) => message.lines().next().unwrap_or_default(),
+                None => e.commit.subject.as_str(),
+            };
+            let mut subject_style = Style::new();
+            if e.action == RebaseAction::Drop {
+                subject_style = subject_style
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT);
+            }
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<7}", action_name(e.action)),
+                    Style::new().fg(action_color(e.action)),
+                ),
+                Span::styled(
+                    format!("{} ", e.commit.short_hash),
+                    Style::new().fg(Color::Yellow),
+                ),
+                Span::styled(subject, subject_style),
+            ]))
+        })
+        .collect();
+
+    let list = widgets::List::new(items).block(block);
+    if focused {
+        return list.highlight_style(Style::new().bg(Color::Yellow));
+    }
+    list
+}
@@ -69,6 +69,14 @@ pub fn load_changed_files() -> std::io::Result<Vec<ChangedFile>> {
     Ok(changed_files)
 }
 
+pub fn has_staged_changes() -> bool {
+    Command::new("git")
+        .args(["diff", "--cached", "--quiet"])
+        .status()
+        .map(|s| !s.success())
+        .unwrap_or(false)
+}
+
 pub fn add_file(path: &str) -> std::io::Result<()> {
     let status = Command::new("git").args(["add", "--", path]).status()?;
     if status.success() {
@@ -0,0 +1,170 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{self, List, ListItem},
+};
+
+use crate::state::{BranchTab, TagInfo};
+
+// newest first; `*objectname` is the commit an annotated tag points to and is
+// empty for lightweight tags
+pub fn load_tags() -> Vec<TagInfo> {
+    let output = Command::new("git")
+        .args([
+            "for-each-ref",
+            "--sort=-creatordate",
+            "--format=%(refname:short)%00%(objectname)%00%(*objectname)%00%(creatordate:unix)%00%(contents:subject)",
+            "refs/tags",
+        ])
+        .output()
+        .map(|o| o.stdout)
+        .unwrap_or_default();
+
+    String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let f: Vec<&str> = line.split('\0').collect();
+            if f.len() < 5 {
+                return None;
+            }
+            let annotated = !f[2].is_empty();
+            Some(TagInfo {
+                name: f[0].to_string(),
+                target: if annotated { f[2] } else { f[1] }.to_string(),
+                date: f[3].parse().unwrap_or(0),
+                annotated,
+                subject: f[4].to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn widget(tags: &[TagInfo], focused: bool) -> List<'_> {
+    let block = widgets::Block::bordered().title(crate::git_branch::tabs_title(BranchTab::Tags));
+
+    let items: Vec<ListItem> = tags
+        .iter()
+        .map(|t| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{} ", &t.target[..t.target.len().min(7)]),
+                    Style::new().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    format!("{:>4} ", crate::git_commits::relative_date(t.date)),
+                    Style::new().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    t.name.as_str(),
+                    Style::new().fg(if t.annotated {
+                        Color::Yellow
+                    } else {
+                        Color::White
+                    }),
+                ),
+                Span::styled(
+                    if t.annotated {
+                        format!(" {}", t.subject)
+                    } else {
+                        String::new()
+                    },
+                    Style::new().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = widgets::List::new(items).block(block);
+    if focused {
+        return list.highlight_style(Style::new().bg(Color::Yellow));
+    }
+    list
+}
+
+pub fn create_annotated_tag(name: &str, message: &str, hash: &str) -> io::Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(io::Error::other("tag name cannot be empty"));
+    }
+    // an annotated tag needs a message, fall back to the tag name
+    let message = match message.trim() {
+        "" => name,
+        m => m,
+    };
+    let status = Command::new("git")
+        .args(["tag", "-a", name, "-m", message, hash])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("git tag {} failed", name)))
+    }
+}
+
+pub fn delete_tag(name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["tag", "-d", name])
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("git tag -d {} failed", name)))
+    }
+}
+
+pub fn delete_remote_tag(remote: &str, name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["push", "--quiet", remote, "--delete"])
+        .arg(format!("refs/tags/{}", name))
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "deleting tag {} on {} failed",
+            name, remote
+        )))
+    }
+}
+
+pub fn push_tag(remote: &str, name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["push", "--quiet", remote])
+        .arg(format!("refs/tags/{}", name))
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "pushing tag {} to {} failed",
+            name, remote
+        )))
+    }
+}
+
+pub fn checkout_tag(name: &str) -> io::Result<()> {
+    let status = Command::new("git")
+        .args(["checkout", "--quiet"])
+        .arg(format!("refs/tags/{}", name))
+        .stderr(Stdio::null())
+        .stdout(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("git checkout {} failed", name)))
+    }
+}
@@ -2,15 +2,21 @@ mod events;
 mod git_branch;
 mod git_commits;
 mod git_diff;
+mod git_rebase;
 mod git_status;
+mod git_tag;
 mod state;
+mod syntax;
 
 use crate::state::AppState;
+use crate::state::BranchTab;
 use crate::state::CurrentPanel;
+use crate::state::load_more_commits;
+use crate::state::needs_more_commits;
 use crate::state::refresh_all_states;
 use events::handle_events;
 use ratatui::Frame;
-use ratatui::layout::{Constraint, Layout};
+use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::Color;
 use ratatui::style::Style;
 use ratatui::widgets::Block;
@@ -37,9 +43,14 @@ fn run_loop(
     app: &mut AppState,
 ) -> std::io::Result<()> {
     loop {
+        app.commits_loading = needs_more_commits(app);
         terminal
             .draw(|frame| draw(frame, app))
             .expect("failed to draw frame");
+        if app.commits_loading {
+            load_more_commits(app);
+            continue;
+        }
         if handle_events(app)? {
             break Ok(());
         }
@@ -60,36 +71,135 @@ fn draw(frame: &mut Frame, app: &mut AppState) {
         left_top,
         &mut app.status_state,
     );
-    frame.render_stateful_widget(
-        git_branch::widget(
-            &app.branches,
-            matches!(app.current_panel, CurrentPanel::Branch),
+    match app.branch_tab {
+        BranchTab::Local => frame.render_stateful_widget(
+            git_branch::widget(
+                &app.branches,
+                matches!(app.current_panel, CurrentPanel::Branch),
+            ),
+            left_middle,
+            &mut app.branch_state,
         ),
-        left_middle,
-        &mut app.branch_state,
-    );
-    frame.render_stateful_widget(
-        git_commits::widget(
-            &app.commits,
-            matches!(app.current_panel, CurrentPanel::Commit),
+        BranchTab::Remotes => frame.render_stateful_widget(
+            git_branch::remote_widget(
+                &app.remote_branches,
+                matches!(app.current_panel, CurrentPanel::Remote),
+            ),
+            left_middle,
+            &mut app.remote_branch_state,
         ),
-        left_down,
-        &mut app.commit_state,
-    );
+        BranchTab::Tags => frame.render_stateful_widget(
+            git_tag::widget(&app.tags, matches!(app.current_panel, CurrentPanel::Tag)),
+            left_middle,
+            &mut app.tag_state,
+        ),
+    }
+    let commit_focused = matches!(app.current_panel, CurrentPanel::Commit);
+    let mut diff_area = right_area;
+    if let Some(plan) = &app.rebase_plan {
+        frame.render_stateful_widget(
+            git_rebase::widget(plan, commit_focused),
+            left_down,
+            &mut app.rebase_state,
+        );
+    } else if let Some(detail) = &app.commit_detail {
+        frame.render_stateful_widget(
+            git_commits::detail_widget(detail, commit_focused),
+            left_down,
+            &mut app.commit_detail_state,
+        );
+        let lines = git_commits::detail_lines(&detail.commit);
+        let height = (lines.len() as u16 + 2).min(right_area.height / 2);
+        let [info_area, rest] =
+            Layout::vertical([Constraint::Length(height), Constraint::Fill(1)]).areas(right_area);
+        frame.render_widget(git_commits::detail_info(lines), info_area);
+        diff_area = rest;
+    } else {
+        frame.render_stateful_widget(
+            git_commits::widget(
+                &app.commits,
+                &app.commit_graph,
+                app.commits_loading,
+                &app.cherry_pick_clipboard,
+                git_commits::title(
+                    app.commits_branch.as_deref(),
+                    app.commit_filter
+                        .is_some()
+                        .then_some(app.commit_filter_input.trim()),
+                    app.cherry_pick_clipboard.len(),
+                    app.operation,
+                ),
+                commit_focused,
+            ),
+            left_down,
+            &mut app.commit_state,
+        );
+    }
 
+    app.diff_page_size = diff_area.height.saturating_sub(2);
+    let visual = app
+        .diff_visual_anchor
+        .zip(app.diff_state.selected())
+        .map(|(a, c)| (a.min(c), a.max(c)));
     frame.render_stateful_widget(
-        git_diff::widget(&app.diff, matches!(app.current_panel, CurrentPanel::Diff)),
-        right_area,
+        git_diff::widget(
+            &app.diff,
+            &app.diff_target,
+            matches!(app.current_panel, CurrentPanel::Diff),
+            visual,
+            diff_area.width,
+            app.diff_search.as_ref(),
+        ),
+        diff_area,
         &mut app.diff_state,
     );
 
+    render_diff_search_prompt(frame, app, diff_area);
+    render_operation_popup(frame, app);
+    render_revert_popup(frame, app);
     render_commit_popup(frame, app);
     render_branch_popup(frame, app);
+    render_branch_rename_popup(frame, app);
+    render_branch_delete_popup(frame, app);
+    render_upstream_popup(frame, app);
+    render_tag_popup(frame, app);
+    render_tag_delete_popup(frame, app);
+    render_remote_delete_popup(frame, app);
+    render_commit_filter_popup(frame, app);
     render_reset_popup(frame, app);
+    render_diff_options_popup(frame, app);
     render_conflict_popup(frame, app);
     render_push_force_popup(frame, app);
 }
 
+fn render_diff_search_prompt(frame: &mut Frame<'_>, app: &mut AppState, area: Rect) {
+    if !app.diff_search_open {
+        return;
+    }
+    let Some(search) = &app.diff_search else {
+        return;
+    };
+    let [_, prompt_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]).areas(area);
+
+    frame.render_widget(Clear, prompt_area);
+    let title = format!(
+        "Search [{}] [{}] (Enter确认，Esc取消，Ctrl-r正则，Ctrl-t大小写)",
+        if search.regex { "regex" } else { "text" },
+        if search.ignore_case {
+            "ignore case"
+        } else {
+            "match case"
+        },
+    );
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let para = Paragraph::new(format!("/{}", search.query))
+        .block(block)
+        .style(Style::default().fg(Color::White));
+
+    frame.render_widget(para, prompt_area);
+}
+
 fn render_commit_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     if !app.commit_popup_open {
         return;
@@ -114,7 +224,10 @@ fn render_commit_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     let title = match app.commit_popup_mode {
         crate::state::CommitPopupMode::New => "Commit Message (Enter to submit, Esc to cancel)",
         crate::state::CommitPopupMode::Edit => {
-            "Edit Commit Message (Enter to amend, Esc to cancel)"
+            "Reword Commit Message (Enter to reword, Esc to cancel)"
+        }
+        crate::state::CommitPopupMode::RebaseReword => {
+            "Reword in rebase plan (Enter to confirm, Esc to cancel)"
         }
     };
     let block = Block::default().borders(Borders::ALL).title(title);
@@ -156,6 +269,310 @@ fn render_branch_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     frame.render_widget(para, popup_area);
 }
 
+fn render_branch_rename_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.branch_rename_popup_open {
+        return;
+    }
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(3),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Rename Branch (Enter to rename, Esc to cancel)");
+    let para = Paragraph::new(app.branch_rename_input.clone())
+        .block(block)
+        .style(Style::default().fg(Color::White));
+
+    frame.render_widget(para, popup_area);
+}
+
+fn render_branch_delete_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.branch_delete_popup_open {
+        return;
+    }
+    let Some(branch) = app
+        .branch_state
+        .selected()
+        .and_then(|idx| app.branches.get(idx))
+    else {
+        return;
+    };
+    let name = git_branch::normalize_branch_name(&branch.name);
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(if app.branch_delete_upstream.is_some() {
+            4
+        } else {
+            3
+        }),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let verb = if app.branch_delete_unmerged {
+        "force delete"
+    } else {
+        "delete"
+    };
+    let items: Vec<ratatui::widgets::ListItem> =
+        std::iter::once(format!("{} {} (本地删除)", verb, name))
+            .chain(
+                app.branch_delete_upstream
+                    .iter()
+                    .map(|u| format!("{} {} + {} (同时删除远端)", verb, name, u.name)),
+            )
+            .map(ratatui::widgets::ListItem::new)
+            .collect();
+
+    let mut block = Block::default().borders(Borders::ALL);
+    if app.branch_delete_unmerged {
+        block = block
+            .title("未合并到 HEAD，将强制删除 (↑/↓选择，Enter确定，Esc取消)")
+            .border_style(Style::default().fg(Color::Red));
+    } else {
+        block = block.title("Delete branch (↑/↓选择，Enter确定，Esc取消)");
+    }
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.branch_delete_state);
+}
+
+fn render_upstream_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.upstream_popup_open {
+        return;
+    }
+
+    let v = Layout::vertical([
+        Constraint::Percentage(25),
+        Constraint::Length((app.remote_branches.len() as u16 + 2).min(15)),
+        Constraint::Percentage(25),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let items: Vec<ratatui::widgets::ListItem> = app
+        .remote_branches
+        .iter()
+        .map(|r| ratatui::widgets::ListItem::new(r.name.as_str()))
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Set upstream (↑/↓选择，Enter确定，Esc取消)");
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.upstream_state);
+}
+
+fn render_commit_filter_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.commit_filter_open {
+        return;
+    }
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(3),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Filter commits: text author: path: since: until: -S: -G: (Enter确认，Esc取消)");
+    let para = Paragraph::new(app.commit_filter_input.clone())
+        .block(block)
+        .style(Style::default().fg(Color::White));
+
+    frame.render_widget(para, popup_area);
+}
+
+fn render_tag_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.tag_popup_open {
+        return;
+    }
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(6),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+    let [name_area, message_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Length(3)]).areas(popup_area);
+
+    frame.render_widget(Clear, popup_area);
+    let focused = |on: bool| {
+        if on {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        }
+    };
+    let name = Paragraph::new(app.tag_name_input.clone())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(focused(!app.tag_popup_message_focus))
+                .title("New annotated tag (Tab切换，Enter创建，Esc取消)"),
+        )
+        .style(Style::default().fg(Color::White));
+    let message = Paragraph::new(app.tag_message_input.clone())
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(focused(app.tag_popup_message_focus))
+                .title("Message (empty: tag name)"),
+        )
+        .style(Style::default().fg(Color::White));
+
+    frame.render_widget(name, name_area);
+    frame.render_widget(message, message_area);
+}
+
+fn render_tag_delete_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.tag_delete_popup_open {
+        return;
+    }
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(app.tag_delete_remotes.len() as u16 + 3),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let items: Vec<ratatui::widgets::ListItem> =
+        std::iter::once("local only (本地删除)".to_string())
+            .chain(
+                app.tag_delete_remotes
+                    .iter()
+                    .map(|r| format!("local + {} (同时删除远端)", r)),
+            )
+            .map(ratatui::widgets::ListItem::new)
+            .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Delete tag (↑/↓选择，Enter确定，Esc取消)");
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.tag_delete_state);
+}
+
+fn render_remote_delete_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.remote_delete_popup_open {
+        return;
+    }
+    let Some(branch) = app
+        .remote_branch_state
+        .selected()
+        .and_then(|idx| app.remote_branches.get(idx))
+    else {
+        return;
+    };
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(5),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Delete remote branch (Enter/y确认，Esc/n取消)");
+    let para = Paragraph::new(format!(
+        "从 {} 删除分支 {}？\n(git push {} --delete {})",
+        branch.remote, branch.branch, branch.remote, branch.branch
+    ))
+    .block(block)
+    .style(Style::default().fg(Color::Red));
+    frame.render_widget(para, popup_area);
+}
+
 fn render_reset_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     if !app.reset_popup_open {
         return;
@@ -200,6 +617,165 @@ fn render_reset_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     frame.render_stateful_widget(list, popup_area, &mut app.reset_state);
 }
 
+fn render_diff_options_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.diff_options_popup_open {
+        return;
+    }
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(7),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let opts = app.diff_options;
+    let check = |on: bool| if on { "[x]" } else { "[ ]" };
+    let options = [
+        format!("{} Ignore whitespace (-w)", check(opts.ignore_whitespace)),
+        format!("    Context lines: {}", opts.context_lines),
+        format!(
+            "    Algorithm: {}",
+            git_diff::algorithm_name(opts.algorithm)
+        ),
+        format!("{} Detect renames (-M)", check(opts.find_renames)),
+        format!("{} Detect copies (-C)", check(opts.find_copies)),
+    ];
+    let items: Vec<ratatui::widgets::ListItem> = options
+        .iter()
+        .map(|s| ratatui::widgets::ListItem::new(s.as_str()))
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Diff options (↑/↓选择，space切换，+/-调整，Esc关闭)");
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.diff_options_state);
+}
+
+fn render_operation_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.operation_popup_open {
+        return;
+    }
+    let Some(op) = app.operation else {
+        return;
+    };
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(5),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let options = [
+        "continue (解决冲突/修改完成后继续)",
+        "skip     (跳过当前提交)",
+        "abort    (放弃并恢复到开始前的状态)",
+    ];
+    let items: Vec<ratatui::widgets::ListItem> = options
+        .iter()
+        .map(|s| ratatui::widgets::ListItem::new(*s))
+        .collect();
+
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "{} in progress (↑/↓选择，Enter确定，Esc取消，c/s/a快速选择)",
+        git_rebase::operation_name(op)
+    ));
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.operation_state);
+}
+
+fn render_revert_popup(frame: &mut Frame<'_>, app: &mut AppState) {
+    if !app.revert_popup_open {
+        return;
+    }
+    let Some(commit) = app
+        .pending_revert_hash
+        .as_ref()
+        .and_then(|hash| app.commits.iter().find(|c| &c.hash == hash))
+    else {
+        return;
+    };
+
+    let v = Layout::vertical([
+        Constraint::Percentage(40),
+        Constraint::Length(commit.parents.len() as u16 + 2),
+        Constraint::Percentage(40),
+    ])
+    .areas(frame.area());
+    let [_, mid_area, _] = v;
+
+    let h = Layout::horizontal([
+        Constraint::Percentage(25),
+        Constraint::Percentage(50),
+        Constraint::Percentage(25),
+    ])
+    .areas(mid_area);
+    let [_, popup_area, _] = h;
+
+    frame.render_widget(Clear, popup_area);
+
+    let items: Vec<ratatui::widgets::ListItem> = commit
+        .parents
+        .iter()
+        .enumerate()
+        .map(|(i, parent)| {
+            let subject = app
+                .commits
+                .iter()
+                .find(|c| &c.hash == parent)
+                .map_or("", |c| c.subject.as_str());
+            ratatui::widgets::ListItem::new(format!(
+                "-m {}  {} {}",
+                i + 1,
+                &parent[..parent.len().min(7)],
+                subject
+            ))
+        })
+        .collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Revert merge: keep which parent (mainline)? (↑/↓选择，Enter确定，Esc取消)");
+
+    let list = ratatui::widgets::List::new(items)
+        .block(block)
+        .highlight_style(Style::default().bg(Color::Yellow));
+
+    frame.render_stateful_widget(list, popup_area, &mut app.revert_state);
+}
+
 fn render_conflict_popup(frame: &mut Frame<'_>, app: &mut AppState) {
     if !app.conflict_popup_open {
         return;
@@ -1,26 +1,100 @@
-use ratatui::widgets::ListState;
+use std::{collections::HashSet, ops::Range};
+
+use ratatui::{style::Color, widgets::ListState};
+use regex::Regex;
 
 use crate::{
-    git_branch::load_branches, git_commits::load_commits, git_diff::load_diff,
-    git_status::load_changed_files,
+    git_branch::{load_branches, load_remote_branches},
+    git_commits::{CommitStream, extend_graph, load_commit_files, load_ref_labels, rev_hash},
+    git_diff::{build_rows, load_diff, update_search_hits},
+    git_rebase::current_operation,
+    git_status::{is_staged_index_code, load_changed_files},
+    git_tag::load_tags,
+    syntax::highlight_diff,
 };
 
+const COMMIT_PAGE_SIZE: usize = 300;
+// rows left below the selection when the next page is requested
+const COMMIT_LOAD_AHEAD: usize = 50;
+
 #[derive(Default)]
 pub struct AppState {
     pub branches: Vec<BranchInfo>,
-    pub commits: Vec<String>,
-    pub diff: String,
+    pub remote_branches: Vec<RemoteBranchInfo>,
+    pub tags: Vec<TagInfo>,
+    pub commits: Vec<CommitInfo>,
+    pub commit_graph: CommitGraph,
+    // branch whose log is browsed instead of HEAD's
+    pub commits_branch: Option<String>,
+    pub commit_filter: Option<CommitFilter>,
+    // tip the loaded commit pages belong to
+    pub commits_head: Option<String>,
+    // log the next page is read from
+    pub commit_stream: Option<CommitStream>,
+    // the last page came back short, there is nothing more to load
+    pub commits_exhausted: bool,
+    // the next page is loaded after the "loading more" row has been drawn
+    pub commits_loading: bool,
+    // commit detail view, replaces the commit list while open
+    pub commit_detail: Option<CommitDetail>,
+    pub commit_detail_state: ListState,
+
+    // commits copied for cherry-picking onto the current branch
+    pub cherry_pick_clipboard: Vec<CommitInfo>,
+
+    // commit filter prompt, `commit_filter_input` is kept to edit the active filter
+    pub commit_filter_open: bool,
+    pub commit_filter_input: String,
+
+    // interactive rebase editor, replaces the commit list while open
+    pub rebase_plan: Option<RebasePlan>,
+    pub rebase_state: ListState,
+
+    // mainline parent popup for reverting a merge commit
+    pub revert_popup_open: bool,
+    pub revert_state: ListState, // index of the parent, `-m` is this + 1
+    pub pending_revert_hash: Option<String>,
+
+    // rebase, cherry-pick or revert waiting for conflict resolution or an `edit` stop
+    pub operation: Option<GitOperation>,
+    pub operation_popup_open: bool,
+    pub operation_state: ListState, // 0: continue, 1: skip, 2: abort
+    pub diff: Diff,
+    pub diff_target: DiffTarget,
     pub changed_files: Vec<ChangedFile>,
     pub status_state: ListState,
     pub branch_state: ListState,
+    pub remote_branch_state: ListState,
+    pub tag_state: ListState,
+    // tab shown in the middle left panel
+    pub branch_tab: BranchTab,
     pub commit_state: ListState,
     pub diff_state: ListState,
     pub current_panel: CurrentPanel,
 
+    // diff visual line selection, anchored at this row
+    pub diff_visual_anchor: Option<usize>,
+    pub diff_syntax_highlight: bool,
+    pub diff_split: bool,
+    pub diff_folded: HashSet<(DiffSource, String)>,
+    // rows visible in the diff panel, updated on every draw
+    pub diff_page_size: u16,
+
+    // diff search prompt, the search stays active after the prompt closes
+    pub diff_search_open: bool,
+    pub diff_search: Option<DiffSearch>,
+
+    // diff options popup
+    pub diff_options_popup_open: bool,
+    pub diff_options_state: ListState,
+    pub diff_options: DiffOptions,
+
     // commit popup related
     pub commit_popup_open: bool,
     pub commit_input: String,
     pub commit_popup_mode: CommitPopupMode,
+    // commit reworded by the `Edit` mode
+    pub pending_reword_hash: Option<String>,
 
     // reset popup related
     pub reset_popup_open: bool,
@@ -31,6 +105,37 @@ pub struct AppState {
     pub branch_popup_open: bool,
     pub branch_input: String,
 
+    // upstream popup, tracks one of `remote_branches` with the selected branch
+    pub upstream_popup_open: bool,
+    pub upstream_state: ListState,
+
+    // branch rename popup, prefilled with the selected branch's name
+    pub branch_rename_popup_open: bool,
+    pub branch_rename_input: String,
+
+    // branch delete popup: local only, or local and its upstream on the remote;
+    // `git branch -D` is used when the branch is not merged into HEAD
+    pub branch_delete_popup_open: bool,
+    pub branch_delete_state: ListState,
+    pub branch_delete_unmerged: bool,
+    pub branch_delete_upstream: Option<RemoteBranchInfo>,
+
+    // annotated tag popup, tags `pending_tag_hash`
+    pub tag_popup_open: bool,
+    pub tag_name_input: String,
+    pub tag_message_input: String,
+    // typing into the message instead of the name
+    pub tag_popup_message_focus: bool,
+    pub pending_tag_hash: Option<String>,
+
+    // tag delete popup: local only, or local and on one of `tag_delete_remotes`
+    pub tag_delete_popup_open: bool,
+    pub tag_delete_state: ListState,
+    pub tag_delete_remotes: Vec<String>,
+
+    // confirms deleting the selected remote branch on its remote
+    pub remote_delete_popup_open: bool,
+
     // conflict alert popup
     pub conflict_popup_open: bool,
     pub conflict_message: String,
@@ -45,15 +150,27 @@ pub enum CurrentPanel {
     #[default]
     Status,
     Branch,
+    Remote,
+    Tag,
     Commit,
     Diff,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum BranchTab {
+    #[default]
+    Local,
+    Remotes,
+    Tags,
+}
+
 #[derive(Default, Clone, Copy)]
 pub enum CommitPopupMode {
     #[default]
     New,
     Edit,
+    // message of the selected rebase plan entry
+    RebaseReword,
 }
 
 pub struct ChangedFile {
@@ -62,28 +179,304 @@ pub struct ChangedFile {
     pub path: String,
 }
 
+#[derive(Clone, Default)]
+pub struct CommitInfo {
+    pub hash: String,
+    pub short_hash: String,
+    pub author_name: String,
+    pub author_email: String,
+    // unix timestamps
+    pub author_date: i64,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date: i64,
+    pub subject: String,
+    pub body: String,
+    pub parents: Vec<String>,
+    // decorations such as "HEAD -> main", "origin/main", "tag: v1.0"
+    pub refs: Vec<String>,
+}
+
+// Enter on a commit: its metadata plus the files it changed
+pub struct CommitDetail {
+    pub commit: CommitInfo,
+    pub files: Vec<CommitFile>,
+}
+
+pub struct CommitFile {
+    // `git diff --raw` status letter
+    pub status: char,
+    pub path: String,
+    // source path of a rename or copy
+    pub old_path: Option<String>,
+    // `None` for binary files
+    pub additions: Option<u32>,
+    pub deletions: Option<u32>,
+}
+
+// narrows the Commits panel, empty fields are not applied
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct CommitFilter {
+    // `--grep`, case-insensitive
+    pub message: String,
+    pub author: String,
+    pub path: String,
+    pub since: String,
+    pub until: String,
+    // `-S`, commits changing the number of occurrences of a string
+    pub pickaxe: String,
+    // `-G`, commits whose diff matches a regex
+    pub pickaxe_regex: String,
+}
+
+pub struct RebasePlan {
+    // commit the entries are replayed onto, `None` when the root commit is included
+    pub base: Option<String>,
+    // newest first, like the commit list
+    pub entries: Vec<RebaseEntry>,
+}
+
+pub struct RebaseEntry {
+    pub action: RebaseAction,
+    pub commit: CommitInfo,
+    // new message of a reworded commit
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RebaseAction {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GitOperation {
+    Rebase,
+    CherryPick,
+    Revert,
+}
+
+// `git log --graph` style topology, one row per entry of `AppState::commits`
+#[derive(Default)]
+pub struct CommitGraph {
+    pub rows: Vec<Vec<GraphCell>>,
+    // commit hash each lane is waiting for, `None` for a free lane
+    pub lanes: Vec<Option<String>>,
+}
+
+// one terminal column of a graph row, colored by the lane it belongs to
+#[derive(Clone, Copy)]
+pub struct GraphCell {
+    pub symbol: char,
+    pub lane: usize,
+}
+
+pub struct TagInfo {
+    pub name: String,
+    // commit the tag points to, peeled for annotated tags
+    pub target: String,
+    pub date: i64,
+    pub annotated: bool,
+    // annotation subject, or the commit subject of a lightweight tag
+    pub subject: String,
+}
+
 #[derive(Clone, Default)]
 pub struct BranchInfo {
     pub name: String,
     pub ahead: u32,
     pub behind: u32,
     pub is_current: bool,
+    // `origin/main`, `None` when the branch tracks nothing
+    pub upstream: Option<String>,
+    // the upstream is configured but its remote branch no longer exists
+    pub upstream_gone: bool,
+}
+
+#[derive(Clone)]
+pub struct RemoteBranchInfo {
+    // `origin/main`
+    pub name: String,
+    pub remote: String,
+    // branch name on the remote, `main`
+    pub branch: String,
+    // tip commit date and subject
+    pub date: i64,
+    pub subject: String,
+}
+
+#[derive(Default)]
+pub struct Diff {
+    pub files: Vec<DiffFile>,
+    pub rows: Vec<DiffRow>,
+}
+
+pub struct DiffFile {
+    pub path: String,
+    pub header: Vec<String>,
+    pub hunks: Vec<DiffHunk>,
+    pub source: DiffSource,
+    // collapsed to its first header line
+    pub folded: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffSource {
+    Unstaged,
+    Staged,
+    // untracked file rendered as an all-added new file
+    Untracked,
+    // commit or branch comparison, cannot be staged
+    Revision,
+}
+
+#[derive(Default)]
+pub struct DiffSearch {
+    pub query: String,
+    pub regex: bool,
+    pub ignore_case: bool,
+    // `None` while the query is not a valid pattern
+    pub matcher: Option<Regex>,
+    pub hits: Vec<usize>,
+    // row the search was started from, incremental matches are looked up from here
+    pub origin: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    pub ignore_whitespace: bool,
+    pub context_lines: u32,
+    pub algorithm: DiffAlgorithm,
+    pub find_renames: bool,
+    pub find_copies: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            ignore_whitespace: false,
+            context_lines: 3,
+            algorithm: DiffAlgorithm::Myers,
+            find_renames: true,
+            find_copies: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+// what the diff panel shows, follows the selection of the last focused panel
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum DiffTarget {
+    #[default]
+    WorkingTree,
+    File {
+        path: String,
+        staged: bool,
+        unstaged: bool,
+        untracked: bool,
+    },
+    Commit(String),
+    // one file of a commit, `old_path` is set for renames and copies
+    CommitFile {
+        hash: String,
+        path: String,
+        old_path: Option<String>,
+    },
+    Branch(String),
+}
+
+#[derive(Clone)]
+pub struct DiffHunk {
+    pub old_start: u32,
+    pub old_count: u32,
+    pub new_start: u32,
+    pub new_count: u32,
+    pub section: String,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Clone)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: String,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    // syntax colors as byte ranges into `content`, empty when highlighting is off
+    pub highlights: Vec<(Color, Range<usize>)>,
+    // changed words of a paired -/+ line, byte ranges into `content`
+    pub emphasis: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+    NoNewline,
+}
+
+// one rendered line of the diff panel, indexes into `Diff::files`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiffRow {
+    Section(DiffSource),
+    FileHeader {
+        file: usize,
+        line: usize,
+    },
+    HunkHeader {
+        file: usize,
+        hunk: usize,
+    },
+    Line {
+        file: usize,
+        hunk: usize,
+        line: usize,
+    },
+    // side-by-side view: old line on the left, new line on the right
+    Pair {
+        file: usize,
+        hunk: usize,
+        old: Option<usize>,
+        new: Option<usize>,
+    },
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RefreshScope {
     Status,
     Branches,
+    Tags,
     Commits,
     Diff,
 }
 
+// panel that has focus while `tab` is shown in the middle left panel
+pub fn branch_tab_panel(tab: BranchTab) -> CurrentPanel {
+    match tab {
+        BranchTab::Local => CurrentPanel::Branch,
+        BranchTab::Remotes => CurrentPanel::Remote,
+        BranchTab::Tags => CurrentPanel::Tag,
+    }
+}
+
 pub fn refresh_all_states(app: &mut AppState) {
     refresh_scopes(
         app,
         &[
             RefreshScope::Status,
             RefreshScope::Branches,
+            RefreshScope::Tags,
             RefreshScope::Commits,
             RefreshScope::Diff,
         ],
@@ -95,6 +488,7 @@ pub fn refresh_scopes(app: &mut AppState, scopes: &[RefreshScope]) {
         match s {
             RefreshScope::Status => refresh_status_scope(app),
             RefreshScope::Branches => refresh_branches_scope(app),
+            RefreshScope::Tags => refresh_tags_scope(app),
             RefreshScope::Commits => refresh_commits_scope(app),
             RefreshScope::Diff => refresh_diff_scope(app),
         }
@@ -103,6 +497,7 @@ pub fn refresh_scopes(app: &mut AppState, scopes: &[RefreshScope]) {
 
 fn refresh_status_scope(app: &mut AppState) {
     let prev_status_idx = app.status_state.selected();
+    app.operation = current_operation();
     if let Ok(changed_files) = load_changed_files() {
         app.changed_files = changed_files;
         let len = app.changed_files.len();
@@ -125,11 +520,60 @@ fn refresh_branches_scope(app: &mut AppState) {
         let idx = prev_branch_idx.unwrap_or(0).min(len.saturating_sub(1));
         app.branch_state.select(Some(idx));
     }
+
+    let prev_remote_idx = app.remote_branch_state.selected();
+    app.remote_branches = load_remote_branches();
+    let len = app.remote_branches.len();
+    if len == 0 {
+        app.remote_branch_state.select(None);
+    } else {
+        let idx = prev_remote_idx.unwrap_or(0).min(len.saturating_sub(1));
+        app.remote_branch_state.select(Some(idx));
+    }
+}
+
+fn refresh_tags_scope(app: &mut AppState) {
+    let prev_tag_idx = app.tag_state.selected();
+    app.tags = load_tags();
+    let len = app.tags.len();
+    if len == 0 {
+        app.tag_state.select(None);
+    } else {
+        let idx = prev_tag_idx.unwrap_or(0).min(len.saturating_sub(1));
+        app.tag_state.select(Some(idx));
+    }
 }
 
 fn refresh_commits_scope(app: &mut AppState) {
     let prev_commit_idx = app.commit_state.selected();
-    app.commits = load_commits();
+    let head = rev_hash(commits_rev(app));
+    if head.is_some() && head == app.commits_head {
+        // history is unchanged, only refs may have moved
+        let mut labels = load_ref_labels();
+        for c in &mut app.commits {
+            c.refs = labels.remove(&c.hash).unwrap_or_default();
+        }
+    } else {
+        app.commit_stream = CommitStream::open(commits_rev(app), app.commit_filter.as_ref());
+        app.commits = app
+            .commit_stream
+            .as_mut()
+            .map(|stream| stream.next_page(COMMIT_PAGE_SIZE))
+            .unwrap_or_default();
+        app.commits_exhausted = app.commits.len() < COMMIT_PAGE_SIZE;
+        app.commit_graph = CommitGraph::default();
+        // a filtered log has gaps, parents would never show up in the graph
+        if app.commit_filter.is_none() {
+            extend_graph(&mut app.commit_graph, &app.commits);
+        }
+        app.commits_head = head;
+    }
+    if let Some(detail) = &mut app.commit_detail {
+        match app.commits.iter().find(|c| c.hash == detail.commit.hash) {
+            Some(c) => detail.commit = c.clone(),
+            None => app.commit_detail = None,
+        }
+    }
     let len = app.commits.len();
     if len == 0 {
         app.commit_state.select(None);
@@ -139,12 +583,172 @@ fn refresh_commits_scope(app: &mut AppState) {
     }
 }
 
+// revision the Commits panel lists
+fn commits_rev(app: &AppState) -> &str {
+    app.commits_branch.as_deref().unwrap_or("HEAD")
+}
+
+// true when the Commits panel lists HEAD's complete history, which history
+// editing relies on
+pub fn is_head_log(app: &AppState) -> bool {
+    app.commits_branch.is_none() && app.commit_filter.is_none()
+}
+
+pub fn filter_commits(app: &mut AppState, filter: Option<CommitFilter>) {
+    app.commit_filter = filter;
+    app.commits_head = None;
+    app.commit_detail = None;
+    app.commit_state.select(Some(0));
+    refresh_scopes(app, &[RefreshScope::Commits, RefreshScope::Diff]);
+}
+
+// switches the Commits panel between HEAD's log (`None`) and another branch's
+pub fn browse_commits(app: &mut AppState, branch: Option<String>) {
+    app.commits_branch = branch;
+    app.commits_head = None;
+    app.commit_detail = None;
+    app.commit_state.select(Some(0));
+    refresh_scopes(app, &[RefreshScope::Commits, RefreshScope::Diff]);
+}
+
+// true when the selection is close enough to the end of the loaded commits
+// that the next page should be fetched
+pub fn needs_more_commits(app: &AppState) -> bool {
+    !app.commits_exhausted
+        && !app.commits.is_empty()
+        && app.commit_state.selected().unwrap_or(0) + COMMIT_LOAD_AHEAD >= app.commits.len()
+}
+
+pub fn open_commit_detail(app: &mut AppState) {
+    let Some(commit) = app
+        .commit_state
+        .selected()
+        .and_then(|idx| app.commits.get(idx))
+    else {
+        return;
+    };
+    let files = load_commit_files(&commit.hash);
+    app.commit_detail_state
+        .select((!files.is_empty()).then_some(0));
+    app.commit_detail = Some(CommitDetail {
+        commit: commit.clone(),
+        files,
+    });
+}
+
+pub fn load_more_commits(app: &mut AppState) {
+    let Some(stream) = &mut app.commit_stream else {
+        app.commits_exhausted = true;
+        return;
+    };
+    let page = stream.next_page(COMMIT_PAGE_SIZE);
+    app.commits_exhausted = page.len() < COMMIT_PAGE_SIZE;
+    if app.commit_filter.is_none() {
+        extend_graph(&mut app.commit_graph, &page);
+    }
+    app.commits.extend(page);
+}
+
 fn refresh_diff_scope(app: &mut AppState) {
     let prev_diff_idx = app.diff_state.selected();
-    app.diff = load_diff();
-    if let Some(idx) = prev_diff_idx {
-        app.diff_state.select(Some(idx));
+    app.diff_visual_anchor = None;
+
+    let target = selected_diff_target(app);
+    let same_target = target == app.diff_target || matches!(app.current_panel, CurrentPanel::Diff);
+    let mut files = load_diff(&target, &app.diff_options);
+    for file in &mut files {
+        file.folded = app.diff_folded.contains(&(file.source, file.path.clone()));
+    }
+    app.diff = Diff {
+        rows: build_rows(&files, app.diff_split),
+        files,
+    };
+    app.diff_target = target;
+    if app.diff_syntax_highlight {
+        highlight_diff(&mut app.diff);
+    }
+    if let Some(search) = &mut app.diff_search {
+        update_search_hits(&app.diff, search);
+    }
+
+    let len = app.diff.rows.len();
+    if len == 0 {
+        app.diff_state.select(None);
+    } else if let Some(idx) = prev_diff_idx
+        && same_target
+    {
+        app.diff_state.select(Some(idx.min(len - 1)));
     } else {
         app.diff_state.select(None);
     }
 }
+
+fn selected_diff_target(app: &AppState) -> DiffTarget {
+    match app.current_panel {
+        CurrentPanel::Status => app
+            .status_state
+            .selected()
+            .and_then(|idx| app.changed_files.get(idx).or(app.changed_files.last()))
+            .map(file_diff_target)
+            .unwrap_or_default(),
+        CurrentPanel::Branch => app
+            .branch_state
+            .selected()
+            .and_then(|idx| app.branches.get(idx).or(app.branches.last()))
+            .map(|b| DiffTarget::Branch(b.name.clone()))
+            .unwrap_or_default(),
+        CurrentPanel::Remote => app
+            .remote_branch_state
+            .selected()
+            .and_then(|idx| app.remote_branches.get(idx).or(app.remote_branches.last()))
+            .map(|b| DiffTarget::Branch(b.name.clone()))
+            .unwrap_or_default(),
+        CurrentPanel::Tag => app
+            .tag_state
+            .selected()
+            .and_then(|idx| app.tags.get(idx).or(app.tags.last()))
+            .map(|t| DiffTarget::Commit(t.target.clone()))
+            .unwrap_or_default(),
+        CurrentPanel::Commit if let Some(plan) = &app.rebase_plan => app
+            .rebase_state
+            .selected()
+            .and_then(|idx| plan.entries.get(idx))
+            .map(|e| DiffTarget::Commit(e.commit.hash.clone()))
+            .unwrap_or_default(),
+        CurrentPanel::Commit if let Some(detail) = &app.commit_detail => app
+            .commit_detail_state
+            .selected()
+            .and_then(|idx| detail.files.get(idx))
+            .map(|f| DiffTarget::CommitFile {
+                hash: detail.commit.hash.clone(),
+                path: f.path.clone(),
+                old_path: f.old_path.clone(),
+            })
+            .unwrap_or_else(|| DiffTarget::Commit(detail.commit.hash.clone())),
+        CurrentPanel::Commit => app
+            .commit_state
+            .selected()
+            .and_then(|idx| app.commits.get(idx).or(app.commits.last()))
+            .map(|c| DiffTarget::Commit(c.hash.clone()))
+            .unwrap_or_default(),
+        // keep showing the same thing, but pick up staging changes made from the diff panel
+        CurrentPanel::Diff => match &app.diff_target {
+            DiffTarget::File { path, .. } => app
+                .changed_files
+                .iter()
+                .find(|f| &f.path == path)
+                .map(file_diff_target)
+                .unwrap_or_default(),
+            target => target.clone(),
+        },
+    }
+}
+
+fn file_diff_target(file: &ChangedFile) -> DiffTarget {
+    DiffTarget::File {
+        path: file.path.clone(),
+        staged: is_staged_index_code(file.x),
+        unstaged: file.y != ' ' && file.x != '?',
+        untracked: file.x == '?',
+    }
+}
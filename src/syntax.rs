@@ -0,0 +1,80 @@
+use std::{path::Path, sync::OnceLock};
+
+use ratatui::style::Color;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+};
+
+use crate::state::{Diff, DiffLineKind};
+
+// very long lines (minified files) are not worth highlighting
+const MAX_LINE_LEN: usize = 500;
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        themes
+            .themes
+            .remove("base16-ocean.dark")
+            .unwrap_or_default()
+    })
+}
+
+// fills `DiffLine::highlights` for every file whose extension has a known syntax
+pub fn highlight_diff(diff: &mut Diff) {
+    let set = syntax_set();
+    for file in &mut diff.files {
+        let Some(ext) = Path::new(&file.path).extension().and_then(|e| e.to_str()) else {
+            continue;
+        };
+        let Some(syntax) = set.find_syntax_by_extension(ext) else {
+            continue;
+        };
+
+        for hunk in &mut file.hunks {
+            // old and new side are highlighted separately so that removed and
+            // added lines do not corrupt each other's parse state
+            let mut old = HighlightLines::new(syntax, theme());
+            let mut new = HighlightLines::new(syntax, theme());
+
+            for line in &mut hunk.lines {
+                if line.content.len() > MAX_LINE_LEN {
+                    continue;
+                }
+                let text = format!("{}\n", line.content);
+                let ranges = match line.kind {
+                    DiffLineKind::Context => {
+                        let _ = old.highlight_line(&text, set);
+                        new.highlight_line(&text, set)
+                    }
+                    DiffLineKind::Added => new.highlight_line(&text, set),
+                    DiffLineKind::Removed => old.highlight_line(&text, set),
+                    DiffLineKind::NoNewline => continue,
+                };
+                let Ok(ranges) = ranges else {
+                    continue;
+                };
+
+                let mut start = 0;
+                line.highlights = ranges
+                    .iter()
+                    .filter_map(|(style, s)| {
+                        let end = (start + s.len()).min(line.content.len());
+                        let range = start..end;
+                        start += s.len();
+                        let fg = style.foreground;
+                        (!range.is_empty()).then_some((Color::Rgb(fg.r, fg.g, fg.b), range))
+                    })
+                    .collect();
+            }
+        }
+    }
+}